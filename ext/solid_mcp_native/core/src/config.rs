@@ -1,7 +1,139 @@
 //! Configuration for solid-mcp-core
+//!
+//! A [`Config`] is usually built in code with the builder methods. Hosts that
+//! embed the engine without a Ruby layer can load it from the environment
+//! ([`Config::from_env`]) or a TOML file ([`Config::from_toml_file`]).
+//!
+//! Both sources use the field names below. Durations accept a unit suffix
+//! (`250ms`, `30s`, `5m`, `1h`); bare numbers are seconds.
 
+use crate::db::DatabaseUrl;
+use crate::{Error, Result};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::time::Duration;
 
+/// Prefix for configuration environment variables
+pub const ENV_PREFIX: &str = "SOLID_MCP_";
+
+/// Keys accepted by [`Config::set`], [`Config::from_env`] and
+/// [`Config::from_toml_file`]
+pub const KEYS: &[&str] = &[
+    "database_url",
+    "batch_size",
+    "polling_interval",
+    "max_wait_time",
+    "delivered_retention",
+    "undelivered_retention",
+    "max_queue_size",
+    "shutdown_timeout",
+    "metrics_addr",
+    "persist_traceparent",
+    "max_connections",
+    "min_connections",
+    "acquire_timeout",
+    "idle_timeout",
+    "max_lifetime",
+    "statement_cache_size",
+    "sqlite_journal_mode",
+    "sqlite_synchronous",
+    "sqlite_busy_timeout",
+    "sqlite_read_connections",
+    "sqlite_timestamp_format",
+    "table_name",
+    "schema",
+    "channel_prefix",
+];
+
+/// Longest PostgreSQL identifier, in bytes
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+/// Session IDs are UUIDs, so channel prefixes must leave room for 36 bytes
+const MAX_CHANNEL_PREFIX_LEN: usize = MAX_IDENTIFIER_LEN - 36;
+
+/// SQLite `journal_mode` pragma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+impl std::str::FromStr for JournalMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "delete" => Ok(Self::Delete),
+            "truncate" => Ok(Self::Truncate),
+            "persist" => Ok(Self::Persist),
+            "memory" => Ok(Self::Memory),
+            "wal" => Ok(Self::Wal),
+            "off" => Ok(Self::Off),
+            _ => Err(Error::Config(format!(
+                "sqlite_journal_mode must be one of delete, truncate, persist, memory, wal, off, got {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+/// How SQLite timestamps are written
+///
+/// Both formats are accepted when reading.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// `YYYY-MM-DD HH:MM:SS.ffffff` in UTC, as ActiveRecord writes them
+    #[default]
+    ActiveRecord,
+    /// RFC 3339, e.g. `2025-06-24T12:00:00.123456789+00:00`
+    Rfc3339,
+}
+
+impl std::str::FromStr for TimestampFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "active_record" | "activerecord" => Ok(Self::ActiveRecord),
+            "rfc3339" => Ok(Self::Rfc3339),
+            _ => Err(Error::Config(format!(
+                "sqlite_timestamp_format must be active_record or rfc3339, got {:?}",
+                s
+            ))),
+        }
+    }
+}
+
+/// SQLite `synchronous` pragma
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SynchronousMode {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl std::str::FromStr for SynchronousMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Self::Off),
+            "normal" => Ok(Self::Normal),
+            "full" => Ok(Self::Full),
+            "extra" => Ok(Self::Extra),
+            _ => Err(Error::Config(format!(
+                "sqlite_synchronous must be one of off, normal, full, extra, got {:?}",
+                s
+            ))),
+        }
+    }
+}
+
 /// Configuration for the pub/sub engine
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -21,6 +153,9 @@ pub struct Config {
     pub undelivered_retention: Duration,
 
     /// Maximum messages in memory queue (default: 10,000)
+    ///
+    /// Also bounds each session's receive buffer; see
+    /// [`PubSub::receive`](crate::PubSub::receive).
     pub max_queue_size: usize,
 
     /// Maximum time to wait for graceful shutdown (default: 30s)
@@ -28,6 +163,73 @@ pub struct Config {
 
     /// Database URL (required)
     pub database_url: String,
+
+    /// Address for the Prometheus `/metrics` listener (default: disabled)
+    ///
+    /// Requires the `metrics` feature; ignored otherwise.
+    pub metrics_addr: Option<SocketAddr>,
+
+    /// Persist each message's `traceparent` (default: false)
+    ///
+    /// Requires a nullable `traceparent` column on the messages table.
+    pub persist_traceparent: bool,
+
+    /// Maximum pool connections (default: 1 for SQLite, 10 for PostgreSQL)
+    ///
+    /// For SQLite this sizes the read pool; the writer always has a single
+    /// connection.
+    ///
+    /// Takes precedence over a `pool_size` parameter in the database URL.
+    pub max_connections: Option<u32>,
+
+    /// Connections kept open while idle (default: 0)
+    pub min_connections: u32,
+
+    /// Maximum time to wait for a pooled connection (default: 30s)
+    pub acquire_timeout: Duration,
+
+    /// Close connections idle for this long (default: 10 minutes, `None` to keep)
+    pub idle_timeout: Option<Duration>,
+
+    /// Recycle connections after this long (default: 30 minutes, `None` to keep)
+    pub max_lifetime: Option<Duration>,
+
+    /// Prepared statements cached per connection (default: 100)
+    pub statement_cache_size: usize,
+
+    /// SQLite journal mode (default: WAL)
+    pub sqlite_journal_mode: JournalMode,
+
+    /// SQLite synchronous mode (default: Normal)
+    pub sqlite_synchronous: SynchronousMode,
+
+    /// SQLite busy timeout (default: 30s)
+    ///
+    /// A `busy_timeout` parameter in the database URL takes precedence.
+    pub sqlite_busy_timeout: Duration,
+
+    /// Read-only connections for SQLite in WAL mode (default: 4)
+    ///
+    /// Used when neither `max_connections` nor the URL's `pool_size` is set.
+    /// Subscriber reads use these so they don't queue behind writes. In-memory
+    /// databases and other journal modes share the writer pool instead.
+    pub sqlite_read_connections: u32,
+
+    /// How SQLite timestamps are written (default: ActiveRecord's format)
+    ///
+    /// Lets Ruby and Rust writers share a table; both formats are read.
+    pub sqlite_timestamp_format: TimestampFormat,
+
+    /// Messages table (default: `solid_mcp_messages`)
+    pub table_name: String,
+
+    /// PostgreSQL schema holding the table (default: the search path)
+    ///
+    /// Ignored on SQLite.
+    pub schema: Option<String>,
+
+    /// PostgreSQL NOTIFY channel prefix (default: `solid_mcp_`)
+    pub channel_prefix: String,
 }
 
 impl Default for Config {
@@ -41,6 +243,22 @@ impl Default for Config {
             max_queue_size: 10_000,
             shutdown_timeout: Duration::from_secs(30),
             database_url: String::new(),
+            metrics_addr: None,
+            persist_traceparent: false,
+            max_connections: None,
+            min_connections: 0,
+            acquire_timeout: Duration::from_secs(30),
+            idle_timeout: Some(Duration::from_secs(600)),
+            max_lifetime: Some(Duration::from_secs(1800)),
+            statement_cache_size: 100,
+            sqlite_journal_mode: JournalMode::Wal,
+            sqlite_synchronous: SynchronousMode::Normal,
+            sqlite_busy_timeout: Duration::from_secs(30),
+            sqlite_read_connections: 4,
+            sqlite_timestamp_format: TimestampFormat::ActiveRecord,
+            table_name: crate::db::names::DEFAULT_TABLE.to_string(),
+            schema: None,
+            channel_prefix: crate::db::names::DEFAULT_CHANNEL_PREFIX.to_string(),
         }
     }
 }
@@ -66,6 +284,24 @@ impl Config {
         self
     }
 
+    /// Builder pattern: set max wait time for pull-based receives
+    pub fn max_wait_time(mut self, timeout: Duration) -> Self {
+        self.max_wait_time = timeout;
+        self
+    }
+
+    /// Builder pattern: set retention for delivered messages
+    pub fn delivered_retention(mut self, retention: Duration) -> Self {
+        self.delivered_retention = retention;
+        self
+    }
+
+    /// Builder pattern: set retention for undelivered messages
+    pub fn undelivered_retention(mut self, retention: Duration) -> Self {
+        self.undelivered_retention = retention;
+        self
+    }
+
     /// Builder pattern: set max queue size
     pub fn max_queue_size(mut self, size: usize) -> Self {
         self.max_queue_size = size;
@@ -78,20 +314,376 @@ impl Config {
         self
     }
 
+    /// Builder pattern: serve Prometheus metrics on the given address
+    pub fn metrics_addr(mut self, addr: SocketAddr) -> Self {
+        self.metrics_addr = Some(addr);
+        self
+    }
+
+    /// Builder pattern: persist trace context alongside messages
+    pub fn persist_traceparent(mut self, enabled: bool) -> Self {
+        self.persist_traceparent = enabled;
+        self
+    }
+
+    /// Builder pattern: set maximum pool connections
+    pub fn max_connections(mut self, max: u32) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// Builder pattern: set connections kept open while idle
+    pub fn min_connections(mut self, min: u32) -> Self {
+        self.min_connections = min;
+        self
+    }
+
+    /// Builder pattern: set pool acquire timeout
+    pub fn acquire_timeout(mut self, timeout: Duration) -> Self {
+        self.acquire_timeout = timeout;
+        self
+    }
+
+    /// Builder pattern: set idle connection timeout
+    pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Builder pattern: set maximum connection lifetime
+    pub fn max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.max_lifetime = lifetime;
+        self
+    }
+
+    /// Builder pattern: set per-connection statement cache size
+    pub fn statement_cache_size(mut self, size: usize) -> Self {
+        self.statement_cache_size = size;
+        self
+    }
+
+    /// Builder pattern: set SQLite journal mode
+    pub fn sqlite_journal_mode(mut self, mode: JournalMode) -> Self {
+        self.sqlite_journal_mode = mode;
+        self
+    }
+
+    /// Builder pattern: set SQLite synchronous mode
+    pub fn sqlite_synchronous(mut self, mode: SynchronousMode) -> Self {
+        self.sqlite_synchronous = mode;
+        self
+    }
+
+    /// Builder pattern: set SQLite busy timeout
+    pub fn sqlite_busy_timeout(mut self, timeout: Duration) -> Self {
+        self.sqlite_busy_timeout = timeout;
+        self
+    }
+
+    /// Builder pattern: set SQLite read-only pool size
+    pub fn sqlite_read_connections(mut self, connections: u32) -> Self {
+        self.sqlite_read_connections = connections;
+        self
+    }
+
+    /// Builder pattern: set the SQLite timestamp format
+    pub fn sqlite_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.sqlite_timestamp_format = format;
+        self
+    }
+
+    /// Builder pattern: set the messages table
+    pub fn table_name(mut self, name: impl Into<String>) -> Self {
+        self.table_name = name.into();
+        self
+    }
+
+    /// Builder pattern: set the PostgreSQL schema
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = Some(schema.into());
+        self
+    }
+
+    /// Builder pattern: set the NOTIFY channel prefix
+    pub fn channel_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.channel_prefix = prefix.into();
+        self
+    }
+
+    /// Load configuration from `SOLID_MCP_*` environment variables
+    ///
+    /// Each key in [`KEYS`] is read from `SOLID_MCP_<KEY>` (e.g.
+    /// `SOLID_MCP_BATCH_SIZE`). The database URL falls back to `DATABASE_URL`.
+    /// Unset variables keep their defaults. The result is validated.
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+        for key in KEYS {
+            if let Some(value) = var(&format!("{}{}", ENV_PREFIX, key.to_uppercase())) {
+                config.set(key, &value)?;
+            }
+        }
+        if config.database_url.is_empty()
+            && let Some(url) = var("DATABASE_URL")
+        {
+            config.database_url = url;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load configuration from a TOML file
+    ///
+    /// The file holds top-level keys from [`KEYS`]; unknown keys are rejected.
+    /// Durations may be numbers (seconds) or strings with a unit. The result
+    /// is validated.
+    pub fn from_toml_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| Error::Config(format!("cannot read {}: {}", path.display(), e)))?;
+        Self::from_toml_str(&contents)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), config_message(e))))
+    }
+
+    fn from_toml_str(contents: &str) -> Result<Self> {
+        let table: toml::Table = contents
+            .parse()
+            .map_err(|e: toml::de::Error| Error::Config(e.message().to_string()))?;
+
+        let mut config = Self::default();
+        for (key, value) in &table {
+            let value = match value {
+                toml::Value::String(s) => s.clone(),
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                other => {
+                    return Err(Error::Config(format!(
+                        "{} must be a string, number or boolean, got {}",
+                        key,
+                        other.type_str()
+                    )));
+                }
+            };
+            config.set(key, &value)?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Set a field from its string form
+    ///
+    /// `key` is one of [`KEYS`].
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let value = value.trim();
+        match key {
+            "database_url" => self.database_url = value.to_string(),
+            "batch_size" => self.batch_size = parse_number(key, value)?,
+            "polling_interval" => self.polling_interval = parse_duration(key, value)?,
+            "max_wait_time" => self.max_wait_time = parse_duration(key, value)?,
+            "delivered_retention" => self.delivered_retention = parse_duration(key, value)?,
+            "undelivered_retention" => self.undelivered_retention = parse_duration(key, value)?,
+            "max_queue_size" => self.max_queue_size = parse_number(key, value)?,
+            "shutdown_timeout" => self.shutdown_timeout = parse_duration(key, value)?,
+            "metrics_addr" => {
+                self.metrics_addr = if value.is_empty() {
+                    None
+                } else {
+                    Some(value.parse().map_err(|e| {
+                        Error::Config(format!("invalid metrics_addr {:?}: {}", value, e))
+                    })?)
+                }
+            }
+            "persist_traceparent" => self.persist_traceparent = parse_bool(key, value)?,
+            "max_connections" => {
+                self.max_connections = if value.is_empty() {
+                    None
+                } else {
+                    Some(parse_number(key, value)?)
+                }
+            }
+            "min_connections" => self.min_connections = parse_number(key, value)?,
+            "acquire_timeout" => self.acquire_timeout = parse_duration(key, value)?,
+            "idle_timeout" => self.idle_timeout = parse_optional_duration(key, value)?,
+            "max_lifetime" => self.max_lifetime = parse_optional_duration(key, value)?,
+            "statement_cache_size" => self.statement_cache_size = parse_number(key, value)?,
+            "sqlite_journal_mode" => self.sqlite_journal_mode = value.parse()?,
+            "sqlite_synchronous" => self.sqlite_synchronous = value.parse()?,
+            "sqlite_busy_timeout" => self.sqlite_busy_timeout = parse_duration(key, value)?,
+            "sqlite_read_connections" => self.sqlite_read_connections = parse_number(key, value)?,
+            "sqlite_timestamp_format" => self.sqlite_timestamp_format = value.parse()?,
+            "table_name" => self.table_name = value.to_string(),
+            "schema" => self.schema = Some(value.to_string()).filter(|s| !s.is_empty()),
+            "channel_prefix" => self.channel_prefix = value.to_string(),
+            _ => {
+                return Err(Error::Config(format!(
+                    "unknown key {:?} (expected one of: {})",
+                    key,
+                    KEYS.join(", ")
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that settings are usable
+    ///
+    /// Rejects values that would make the engine spin or stall, such as a
+    /// zero `batch_size` or `polling_interval`.
+    pub fn validate(&self) -> Result<()> {
+        if self.database_url.trim().is_empty() {
+            return Err(Error::Config("database_url is required".to_string()));
+        }
+        self.parse_database_url()?;
+        if self.batch_size == 0 {
+            return Err(Error::Config("batch_size must be at least 1".to_string()));
+        }
+        if self.sqlite_read_connections == 0 {
+            return Err(Error::Config(
+                "sqlite_read_connections must be at least 1".to_string(),
+            ));
+        }
+        if self.max_queue_size == 0 {
+            return Err(Error::Config(
+                "max_queue_size must be at least 1".to_string(),
+            ));
+        }
+
+        for (key, value) in [
+            ("polling_interval", self.polling_interval),
+            ("max_wait_time", self.max_wait_time),
+            ("shutdown_timeout", self.shutdown_timeout),
+            ("acquire_timeout", self.acquire_timeout),
+        ] {
+            if value.is_zero() {
+                return Err(Error::Config(format!("{} must be greater than zero", key)));
+            }
+        }
+
+        if let Some(max) = self.max_connections {
+            if max == 0 {
+                return Err(Error::Config(
+                    "max_connections must be at least 1".to_string(),
+                ));
+            }
+            if self.min_connections > max {
+                return Err(Error::Config(format!(
+                    "min_connections ({}) must not exceed max_connections ({})",
+                    self.min_connections, max
+                )));
+            }
+        }
+
+        let identifiers = [
+            ("table_name", Some(&self.table_name)),
+            ("schema", self.schema.as_ref()),
+        ];
+        for (key, name) in identifiers {
+            let Some(name) = name else { continue };
+            if name.is_empty() || name.len() > MAX_IDENTIFIER_LEN || name.contains('\0') {
+                return Err(Error::Config(format!(
+                    "{} must be 1 to {} bytes without NUL characters, got {:?}",
+                    key, MAX_IDENTIFIER_LEN, name
+                )));
+            }
+        }
+        if self.channel_prefix.len() > MAX_CHANNEL_PREFIX_LEN || self.channel_prefix.contains('\0')
+        {
+            return Err(Error::Config(format!(
+                "channel_prefix must be at most {} bytes without NUL characters, got {:?}",
+                MAX_CHANNEL_PREFIX_LEN, self.channel_prefix
+            )));
+        }
+
+        if self.undelivered_retention < self.delivered_retention {
+            return Err(Error::Config(format!(
+                "undelivered_retention ({:?}) must not be shorter than delivered_retention ({:?})",
+                self.undelivered_retention, self.delivered_retention
+            )));
+        }
+        Ok(())
+    }
+
     /// Check if this is a PostgreSQL connection
     pub fn is_postgres(&self) -> bool {
-        self.database_url.starts_with("postgres://")
-            || self.database_url.starts_with("postgresql://")
+        self.parse_database_url().is_ok_and(|url| url.is_postgres())
     }
 
     /// Check if this is a SQLite connection
     pub fn is_sqlite(&self) -> bool {
-        self.database_url.starts_with("sqlite://")
-            || self.database_url.starts_with("sqlite:")
-            || self.database_url.ends_with(".db")
-            || self.database_url.ends_with(".sqlite")
-            || self.database_url.ends_with(".sqlite3")
+        self.parse_database_url().is_ok_and(|url| url.is_sqlite())
+    }
+
+    /// Parse `database_url` into its backend and connection options
+    pub fn parse_database_url(&self) -> Result<DatabaseUrl> {
+        DatabaseUrl::parse(&self.database_url)
+    }
+}
+
+fn config_message(err: Error) -> String {
+    match err {
+        Error::Config(message) => message,
+        other => other.to_string(),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| {
+        Error::Config(format!(
+            "{} must be a non-negative integer, got {:?}",
+            key, value
+        ))
+    })
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Ok(true),
+        "false" | "0" | "no" | "off" | "" => Ok(false),
+        _ => Err(Error::Config(format!(
+            "{} must be a boolean, got {:?}",
+            key, value
+        ))),
+    }
+}
+
+/// Parse `250ms`, `30s`, `5m`, `1h` or a bare number of seconds
+fn parse_duration(key: &str, value: &str) -> Result<Duration> {
+    let invalid = || {
+        Error::Config(format!(
+            "{} must be a duration like 250ms, 30s, 5m or 1h, got {:?}",
+            key, value
+        ))
+    };
+
+    let (number, scale) = if let Some(n) = value.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = value.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = value.strip_suffix('m') {
+        (n, 60.0)
+    } else if let Some(n) = value.strip_suffix('h') {
+        (n, 3600.0)
+    } else {
+        (value, 1.0)
+    };
+
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    Duration::try_from_secs_f64(number * scale).map_err(|_| invalid())
+}
+
+/// Like [`parse_duration`], with `none`, `0` or an empty value meaning `None`
+fn parse_optional_duration(key: &str, value: &str) -> Result<Option<Duration>> {
+    if value.is_empty() || value.eq_ignore_ascii_case("none") {
+        return Ok(None);
     }
+    let duration = parse_duration(key, value)?;
+    Ok((!duration.is_zero()).then_some(duration))
 }
 
 #[cfg(test)]
@@ -130,4 +722,147 @@ mod tests {
         assert!(Config::new("./test.sqlite3").is_sqlite());
         assert!(!Config::new("sqlite::memory:").is_postgres());
     }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::new("sqlite::memory:").validate().is_ok());
+        assert!(Config::default().validate().is_err());
+
+        let err = Config::new("sqlite::memory:")
+            .batch_size(0)
+            .validate()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "configuration error: batch_size must be at least 1"
+        );
+
+        let err = Config::new("sqlite::memory:")
+            .polling_interval(Duration::ZERO)
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("polling_interval"));
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(
+            parse_duration("k", "250ms").unwrap(),
+            Duration::from_millis(250)
+        );
+        assert_eq!(parse_duration("k", "30s").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("k", "5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(
+            parse_duration("k", "1h").unwrap(),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            parse_duration("k", "0.5").unwrap(),
+            Duration::from_millis(500)
+        );
+        assert!(parse_duration("k", "-1s").is_err());
+        assert!(parse_duration("k", "soon").is_err());
+    }
+
+    #[test]
+    fn test_pool_settings() {
+        let mut config = Config::new("sqlite::memory:");
+        config.set("max_connections", "8").unwrap();
+        config.set("idle_timeout", "none").unwrap();
+        config.set("max_lifetime", "5m").unwrap();
+        config.set("sqlite_journal_mode", "TRUNCATE").unwrap();
+        config.set("sqlite_synchronous", "full").unwrap();
+
+        assert_eq!(config.max_connections, Some(8));
+        assert_eq!(config.idle_timeout, None);
+        assert_eq!(config.max_lifetime, Some(Duration::from_secs(300)));
+        assert_eq!(config.sqlite_journal_mode, JournalMode::Truncate);
+        assert_eq!(config.sqlite_synchronous, SynchronousMode::Full);
+        assert!(config.set("sqlite_journal_mode", "fast").is_err());
+        config.set("sqlite_timestamp_format", "rfc3339").unwrap();
+        assert_eq!(config.sqlite_timestamp_format, TimestampFormat::Rfc3339);
+
+        let err = config.min_connections(9).validate().unwrap_err();
+        assert!(err.to_string().contains("min_connections"));
+        assert!(
+            Config::new("sqlite::memory:")
+                .max_connections(0)
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_table_names() {
+        let config = Config::new("postgres://localhost/app")
+            .table_name("tenant_a_messages")
+            .schema("tenants")
+            .channel_prefix("tenant_a_");
+        assert!(config.validate().is_ok());
+
+        let err = config
+            .clone()
+            .table_name("")
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("table_name"));
+        assert!(
+            config
+                .channel_prefix("a_very_long_prefix_for_channels_")
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let vars = |name: &str| match name {
+            "SOLID_MCP_BATCH_SIZE" => Some("50".to_string()),
+            "SOLID_MCP_POLLING_INTERVAL" => Some("20ms".to_string()),
+            "SOLID_MCP_PERSIST_TRACEPARENT" => Some("true".to_string()),
+            "DATABASE_URL" => Some("sqlite::memory:".to_string()),
+            _ => None,
+        };
+
+        let config = Config::from_vars(vars).unwrap();
+        assert_eq!(config.batch_size, 50);
+        assert_eq!(config.polling_interval, Duration::from_millis(20));
+        assert!(config.persist_traceparent);
+        assert_eq!(config.database_url, "sqlite::memory:");
+
+        let err =
+            Config::from_vars(|name| (name == "SOLID_MCP_BATCH_SIZE").then(|| "lots".to_string()))
+                .unwrap_err();
+        assert!(err.to_string().contains("batch_size"));
+    }
+
+    #[test]
+    fn test_from_toml_file() {
+        let path = std::env::temp_dir().join(format!("solid_mcp_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+database_url = "postgres://localhost/app"
+batch_size = 500
+polling_interval = "50ms"
+delivered_retention = 600
+metrics_addr = "127.0.0.1:9394"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::from_toml_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(config.is_postgres());
+        assert_eq!(config.batch_size, 500);
+        assert_eq!(config.polling_interval, Duration::from_millis(50));
+        assert_eq!(config.delivered_retention, Duration::from_secs(600));
+        assert_eq!(config.metrics_addr, Some("127.0.0.1:9394".parse().unwrap()));
+
+        let err =
+            Config::from_toml_str("database_url = \"sqlite::memory:\"\nbatchsize = 1").unwrap_err();
+        assert!(err.to_string().contains("unknown key \"batchsize\""));
+    }
 }
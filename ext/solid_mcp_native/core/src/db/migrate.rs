@@ -0,0 +1,442 @@
+//! Schema migrations for hosts without Rails
+//!
+//! [`Migrator`] creates and upgrades the messages table (`solid_mcp_messages`
+//! by default) on SQLite and PostgreSQL. Applied versions are recorded in
+//! `<table>_schema_migrations`. The layout matches the Ruby migration, so a
+//! table created by Rails is adopted rather than recreated.
+
+use super::DbPool;
+use super::names::{TableNames, quote_ident, quote_literal};
+use crate::{Error, Result};
+
+/// Latest schema version known to this build
+pub const SCHEMA_VERSION: i64 = 2;
+
+struct Migration {
+    version: i64,
+    /// Column this migration adds; SQLite has no ADD COLUMN IF NOT EXISTS,
+    /// so the SQLite statements are skipped when it already exists
+    adds_column: Option<&'static str>,
+    sqlite: &'static [&'static str],
+    postgres: &'static [&'static str],
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        adds_column: None,
+        sqlite: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS {table} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                session_id VARCHAR(36) NOT NULL,
+                event_type VARCHAR(50) NOT NULL,
+                data TEXT,
+                created_at TEXT NOT NULL,
+                delivered_at TEXT
+            )
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS {session_index}
+            ON {table}(session_id, id)
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS {delivered_index}
+            ON {table}(delivered_at, created_at)
+            "#,
+        ],
+        postgres: &[
+            r#"
+            CREATE TABLE IF NOT EXISTS {table} (
+                id BIGSERIAL PRIMARY KEY,
+                session_id VARCHAR(36) NOT NULL,
+                event_type VARCHAR(50) NOT NULL,
+                data TEXT,
+                created_at TIMESTAMPTZ NOT NULL,
+                delivered_at TIMESTAMPTZ
+            )
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS {session_index}
+            ON {table}(session_id, id)
+            "#,
+            r#"
+            CREATE INDEX IF NOT EXISTS {delivered_index}
+            ON {table}(delivered_at, created_at)
+            "#,
+        ],
+    },
+    Migration {
+        version: 2,
+        adds_column: Some("traceparent"),
+        sqlite: &["ALTER TABLE {table} ADD COLUMN traceparent TEXT"],
+        postgres: &["ALTER TABLE {table} ADD COLUMN IF NOT EXISTS traceparent TEXT"],
+    },
+];
+
+#[cfg(feature = "postgres")]
+const NOTIFY_TRIGGER: &[&str] = &[
+    r#"
+    CREATE OR REPLACE FUNCTION {notify_function}() RETURNS trigger AS $$
+    BEGIN
+        PERFORM pg_notify({channel_prefix} || NEW.session_id, NEW.id::text);
+        RETURN NEW;
+    END;
+    $$ LANGUAGE plpgsql
+    "#,
+    "DROP TRIGGER IF EXISTS {notify_trigger} ON {table}",
+    r#"
+    CREATE TRIGGER {notify_trigger}
+    AFTER INSERT ON {table}
+    FOR EACH ROW EXECUTE FUNCTION {notify_function}()
+    "#,
+];
+
+/// Applies pending schema migrations
+#[derive(Debug, Clone, Default)]
+pub struct Migrator {
+    notify_trigger: bool,
+}
+
+impl Migrator {
+    /// Create a migrator with default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder pattern: install a PostgreSQL trigger that NOTIFYs
+    /// `<channel_prefix><session_id>` on every insert
+    ///
+    /// The writer already notifies after each batch, so this is only needed
+    /// when other processes (e.g. Ruby without the native extension) write
+    /// messages. Ignored on SQLite. Disabling it does not drop an installed
+    /// trigger.
+    pub fn notify_trigger(mut self, enabled: bool) -> Self {
+        self.notify_trigger = enabled;
+        self
+    }
+
+    /// Apply pending migrations, returning the versions applied
+    ///
+    /// Uses the pool's [`TableNames`]. Each migration runs in its own
+    /// transaction. Safe to call on every startup; an up-to-date schema is
+    /// left untouched.
+    pub async fn run(&self, db: &DbPool) -> Result<Vec<i64>> {
+        let applied = match db {
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(pool) => run_sqlite(pool.writer_pool(), pool.names()).await?,
+            #[cfg(feature = "postgres")]
+            DbPool::Postgres(pool) => {
+                let applied = run_postgres(pool.pool(), pool.names()).await?;
+                if self.notify_trigger {
+                    for statement in NOTIFY_TRIGGER {
+                        sqlx::query(&render(statement, pool.names()))
+                            .execute(pool.pool())
+                            .await?;
+                    }
+                }
+                applied
+            }
+        };
+
+        if !applied.is_empty() {
+            tracing::info!(
+                ?applied,
+                table = db.names().table_name(),
+                "Applied solid-mcp schema migrations"
+            );
+        }
+        Ok(applied)
+    }
+}
+
+/// Highest applied schema version, or `None` if [`Migrator`] never ran
+pub async fn current_version(db: &DbPool) -> Result<Option<i64>> {
+    let names = db.names();
+    if !table_exists(db, &names.version_table_name(), &names.version_table()).await? {
+        return Ok(None);
+    }
+    let query = format!("SELECT MAX(version) FROM {}", names.version_table());
+    let row: (Option<i64>,) = match db {
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => sqlx::query_as(&query).fetch_one(pool.writer_pool()).await?,
+        #[cfg(feature = "postgres")]
+        DbPool::Postgres(pool) => sqlx::query_as(&query).fetch_one(pool.pool()).await?,
+    };
+    Ok(row.0)
+}
+
+/// Check the schema version at startup
+///
+/// Fails if the messages table is missing, or if [`Migrator`] has run but
+/// the recorded version differs from [`SCHEMA_VERSION`]. Schemas managed
+/// entirely by Rails have no version table and are accepted as-is.
+pub async fn verify(db: &DbPool) -> Result<()> {
+    let names = db.names();
+    if !table_exists(db, names.table_name(), &names.table()).await? {
+        return Err(Error::Config(format!(
+            "table {} does not exist; run the Rails migration or Migrator::run",
+            names.table()
+        )));
+    }
+
+    match current_version(db).await? {
+        Some(version) if version < SCHEMA_VERSION => Err(Error::Config(format!(
+            "schema version {} is older than {}; run Migrator::run to upgrade",
+            version, SCHEMA_VERSION
+        ))),
+        Some(version) if version > SCHEMA_VERSION => Err(Error::Config(format!(
+            "schema version {} is newer than this build supports ({})",
+            version, SCHEMA_VERSION
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Check for a table by its unquoted name (SQLite) or quoted, qualified name
+/// (PostgreSQL)
+async fn table_exists(db: &DbPool, name: &str, qualified: &str) -> Result<bool> {
+    match db {
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => {
+            let _ = qualified;
+            let row: Option<(String,)> =
+                sqlx::query_as("SELECT name FROM sqlite_master WHERE type = 'table' AND name = $1")
+                    .bind(name)
+                    .fetch_optional(pool.writer_pool())
+                    .await?;
+            Ok(row.is_some())
+        }
+        #[cfg(feature = "postgres")]
+        DbPool::Postgres(pool) => {
+            let _ = name;
+            let row: (Option<String>,) = sqlx::query_as("SELECT to_regclass($1)::text")
+                .bind(qualified)
+                .fetch_one(pool.pool())
+                .await?;
+            Ok(row.0.is_some())
+        }
+    }
+}
+
+/// Fill the name placeholders in a migration statement
+fn render(statement: &str, names: &TableNames) -> String {
+    statement
+        .replace("{table}", &names.table())
+        .replace("{session_index}", &names.index("on_session_and_id"))
+        .replace(
+            "{delivered_index}",
+            &names.index("on_delivered_and_created"),
+        )
+        .replace("{notify_function}", &names.related("notify"))
+        .replace(
+            "{notify_trigger}",
+            &quote_ident(&format!("{}_notify", names.table_name())),
+        )
+        .replace("{channel_prefix}", &quote_literal(names.channel_prefix()))
+}
+
+#[cfg(feature = "sqlite")]
+pub(crate) async fn run_sqlite(
+    pool: &sqlx::Pool<sqlx::Sqlite>,
+    names: &TableNames,
+) -> Result<Vec<i64>> {
+    let version_table = names.version_table();
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (version INTEGER PRIMARY KEY, applied_at TEXT NOT NULL)",
+        version_table
+    ))
+    .execute(pool)
+    .await?;
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS {
+        let mut tx = pool.begin().await?;
+        let done: Option<(i64,)> = sqlx::query_as(&format!(
+            "SELECT version FROM {} WHERE version = $1",
+            version_table
+        ))
+        .bind(migration.version)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if done.is_some() {
+            continue;
+        }
+
+        // Adopting a table that already has the column
+        let exists = match migration.adds_column {
+            Some(column) => {
+                let found: Option<(String,)> =
+                    sqlx::query_as("SELECT name FROM pragma_table_info($1) WHERE name = $2")
+                        .bind(names.table_name())
+                        .bind(column)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                found.is_some()
+            }
+            None => false,
+        };
+        if !exists {
+            for statement in migration.sqlite {
+                sqlx::query(&render(statement, names))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {} (version, applied_at) VALUES ($1, $2)",
+            version_table
+        ))
+        .bind(migration.version)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+#[cfg(feature = "postgres")]
+async fn run_postgres(pool: &sqlx::Pool<sqlx::Postgres>, names: &TableNames) -> Result<Vec<i64>> {
+    if let Some(schema) = names.schema() {
+        sqlx::query(&format!(
+            "CREATE SCHEMA IF NOT EXISTS {}",
+            quote_ident(schema)
+        ))
+        .execute(pool)
+        .await?;
+    }
+    let version_table = names.version_table();
+    sqlx::query(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (version BIGINT PRIMARY KEY, applied_at TIMESTAMPTZ NOT NULL)",
+        version_table
+    ))
+    .execute(pool)
+    .await?;
+
+    let mut applied = Vec::new();
+    for migration in MIGRATIONS {
+        let mut tx = pool.begin().await?;
+        // Serialise concurrent migrators of the same table across processes
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(&version_table)
+            .execute(&mut *tx)
+            .await?;
+        let done: Option<(i64,)> = sqlx::query_as(&format!(
+            "SELECT version FROM {} WHERE version = $1",
+            version_table
+        ))
+        .bind(migration.version)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if done.is_some() {
+            continue;
+        }
+
+        for statement in migration.postgres {
+            sqlx::query(&render(statement, names))
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query(&format!(
+            "INSERT INTO {} (version, applied_at) VALUES ($1, now())",
+            version_table
+        ))
+        .bind(migration.version)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        applied.push(migration.version);
+    }
+    Ok(applied)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use crate::db::DatabaseUrl;
+    use crate::db::sqlite::SqlitePool;
+
+    async fn memory_db() -> DbPool {
+        DbPool::Sqlite(SqlitePool::new("sqlite::memory:").await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_run_is_idempotent() {
+        let db = memory_db().await;
+        assert_eq!(current_version(&db).await.unwrap(), None);
+        assert!(verify(&db).await.is_err());
+
+        let applied = Migrator::new().run(&db).await.unwrap();
+        assert_eq!(applied, vec![1, 2]);
+        assert_eq!(current_version(&db).await.unwrap(), Some(SCHEMA_VERSION));
+        verify(&db).await.unwrap();
+
+        assert!(Migrator::new().run(&db).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_adopts_existing_table() {
+        let db = memory_db().await;
+        let DbPool::Sqlite(pool) = &db else {
+            unreachable!()
+        };
+        // Table created outside the migrator, already carrying traceparent
+        sqlx::query(
+            "CREATE TABLE solid_mcp_messages (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             session_id TEXT NOT NULL, event_type TEXT NOT NULL, data TEXT, \
+             created_at TEXT NOT NULL, delivered_at TEXT, traceparent TEXT)",
+        )
+        .execute(pool.writer_pool())
+        .await
+        .unwrap();
+        verify(&db).await.unwrap();
+
+        let applied = Migrator::new().run(&db).await.unwrap();
+        assert_eq!(applied, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_verify_rejects_old_version() {
+        let db = memory_db().await;
+        Migrator::new().run(&db).await.unwrap();
+        let DbPool::Sqlite(pool) = &db else {
+            unreachable!()
+        };
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE version = 2",
+            pool.names().version_table()
+        ))
+        .execute(pool.writer_pool())
+        .await
+        .unwrap();
+
+        let err = verify(&db).await.unwrap_err();
+        assert!(err.to_string().contains("schema version 1 is older"));
+    }
+
+    #[tokio::test]
+    async fn test_custom_table_name() {
+        let config = Config::new("sqlite::memory:").table_name("tenant-a messages");
+        let url = DatabaseUrl::parse(&config.database_url).unwrap();
+        let db = DbPool::Sqlite(SqlitePool::connect(&url, &config).await.unwrap());
+
+        Migrator::new().run(&db).await.unwrap();
+        verify(&db).await.unwrap();
+        crate::db::schema::check(&db, true).await.unwrap();
+
+        let DbPool::Sqlite(pool) = &db else {
+            unreachable!()
+        };
+        let tables: Vec<(String,)> =
+            sqlx::query_as("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+                .fetch_all(pool.writer_pool())
+                .await
+                .unwrap();
+        let tables: Vec<_> = tables.into_iter().map(|(name,)| name).collect();
+        assert!(tables.contains(&"tenant-a messages".to_string()));
+        assert!(!tables.contains(&"solid_mcp_messages".to_string()));
+    }
+}
@@ -2,15 +2,24 @@
 //!
 //! Supports both SQLite and PostgreSQL backends.
 
+pub mod database_url;
+pub mod migrate;
+pub mod names;
 #[cfg(feature = "postgres")]
 pub mod postgres;
+pub mod schema;
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
+use crate::metrics::DbStats;
 use crate::{Config, Message, Result};
 use async_trait::async_trait;
 use std::time::Duration;
 
+pub use database_url::{BackendKind, DatabaseUrl, SqliteMode};
+pub use migrate::{Migrator, SCHEMA_VERSION};
+pub use names::TableNames;
+
 /// Database backend trait
 #[async_trait]
 pub trait Database: Send + Sync + 'static {
@@ -38,6 +47,29 @@ pub trait Database: Send + Sync + 'static {
     async fn max_id(&self) -> Result<i64>;
 }
 
+/// Split decoded rows into messages and IDs of rows that failed to decode
+///
+/// Failed rows are logged. Backends mark them delivered so a single bad row
+/// written by another process can't wedge a subscriber by failing every poll;
+/// cleanup removes them after `delivered_retention`.
+pub(crate) fn quarantine(
+    session_id: &str,
+    rows: Vec<(i64, std::result::Result<Message, sqlx::Error>)>,
+) -> (Vec<Message>, Vec<i64>) {
+    let mut messages = Vec::with_capacity(rows.len());
+    let mut quarantined = Vec::new();
+    for (id, row) in rows {
+        match row {
+            Ok(message) => messages.push(message),
+            Err(e) => {
+                tracing::warn!(id, session_id, error = %e, "Quarantining undecodable message");
+                quarantined.push(id);
+            }
+        }
+    }
+    (messages, quarantined)
+}
+
 /// Database pool type (enum dispatch for runtime selection)
 pub enum DbPool {
     #[cfg(feature = "sqlite")]
@@ -49,26 +81,28 @@ pub enum DbPool {
 impl DbPool {
     /// Create a new database pool from config
     ///
-    /// The database and tables must already exist (created by Ruby migrations).
+    /// The tables must already exist, created by the Ruby migration or
+    /// [`Migrator`].
     pub async fn new(config: &Config) -> Result<Self> {
-        #[cfg(feature = "postgres")]
-        if config.is_postgres() {
-            return Ok(Self::Postgres(
-                postgres::PostgresPool::new(&config.database_url).await?,
-            ));
-        }
-
-        #[cfg(feature = "sqlite")]
-        if config.is_sqlite() {
-            return Ok(Self::Sqlite(
-                sqlite::SqlitePool::new(&config.database_url).await?,
-            ));
+        let url = DatabaseUrl::parse(&config.database_url)?;
+        match url.kind {
+            #[cfg(feature = "postgres")]
+            BackendKind::Postgres { .. } => Ok(Self::Postgres(
+                postgres::PostgresPool::connect(&url, config)
+                    .await?
+                    .with_traceparent(config.persist_traceparent),
+            )),
+            #[cfg(feature = "sqlite")]
+            BackendKind::Sqlite { .. } => Ok(Self::Sqlite(
+                sqlite::SqlitePool::connect(&url, config)
+                    .await?
+                    .with_traceparent(config.persist_traceparent),
+            )),
+            #[allow(unreachable_patterns)]
+            _ => Err(crate::Error::Config(
+                "database backend is not enabled in this build".to_string(),
+            )),
         }
-
-        Err(crate::Error::Config(format!(
-            "Unsupported database URL: {}",
-            config.database_url
-        )))
     }
 
     /// Check if this is a PostgreSQL pool (supports LISTEN/NOTIFY)
@@ -76,17 +110,43 @@ impl DbPool {
         matches!(self, Self::Postgres(_))
     }
 
-    /// Create tables for testing purposes only
-    #[cfg(test)]
-    pub(crate) async fn setup_test_schema(&self) -> Result<()> {
+    /// Table and channel names in use
+    pub fn names(&self) -> &TableNames {
         match self {
             #[cfg(feature = "sqlite")]
-            Self::Sqlite(pool) => pool.setup_test_schema().await,
+            Self::Sqlite(pool) => pool.names(),
             #[cfg(feature = "postgres")]
-            Self::Postgres(_) => {
-                // PostgreSQL tests require pre-existing schema
-                Ok(())
-            }
+            Self::Postgres(pool) => pool.names(),
+        }
+    }
+
+    /// Backend name (`sqlite` or `postgres`)
+    pub fn backend_name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(_) => "sqlite",
+            #[cfg(feature = "postgres")]
+            Self::Postgres(_) => "postgres",
+        }
+    }
+
+    /// Run a trivial query to check the database is reachable
+    pub async fn ping(&self) -> Result<()> {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(pool) => pool.ping().await,
+            #[cfg(feature = "postgres")]
+            Self::Postgres(pool) => pool.ping().await,
+        }
+    }
+
+    /// Snapshot connection pool utilisation
+    pub fn pool_stats(&self) -> DbStats {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(pool) => pool.pool_stats(),
+            #[cfg(feature = "postgres")]
+            Self::Postgres(pool) => pool.pool_stats(),
         }
     }
 }
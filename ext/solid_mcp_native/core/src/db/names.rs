@@ -0,0 +1,142 @@
+//! Table, index and channel names
+//!
+//! [`TableNames`] turns the configured table name, PostgreSQL schema and
+//! NOTIFY channel prefix into quoted identifiers, so several instances can
+//! share one database.
+
+use crate::Config;
+
+/// Default messages table
+pub const DEFAULT_TABLE: &str = "solid_mcp_messages";
+
+/// Default NOTIFY channel prefix
+pub const DEFAULT_CHANNEL_PREFIX: &str = "solid_mcp_";
+
+/// Quoted names derived from [`Config`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableNames {
+    table_name: String,
+    schema: Option<String>,
+    channel_prefix: String,
+}
+
+impl Default for TableNames {
+    fn default() -> Self {
+        Self {
+            table_name: DEFAULT_TABLE.to_string(),
+            schema: None,
+            channel_prefix: DEFAULT_CHANNEL_PREFIX.to_string(),
+        }
+    }
+}
+
+impl TableNames {
+    /// Names for a config; the schema is only kept when `with_schema` is set
+    /// (SQLite has no schemas)
+    pub fn from_config(config: &Config, with_schema: bool) -> Self {
+        Self {
+            table_name: config.table_name.clone(),
+            schema: config.schema.clone().filter(|_| with_schema),
+            channel_prefix: config.channel_prefix.clone(),
+        }
+    }
+
+    /// Unquoted table name
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// Unquoted schema, if set
+    pub fn schema(&self) -> Option<&str> {
+        self.schema.as_deref()
+    }
+
+    /// Quoted, schema-qualified messages table
+    pub fn table(&self) -> String {
+        self.qualify(&self.table_name)
+    }
+
+    /// Unquoted table recording applied migrations
+    pub fn version_table_name(&self) -> String {
+        format!("{}_schema_migrations", self.table_name)
+    }
+
+    /// Quoted, schema-qualified table recording applied migrations
+    pub fn version_table(&self) -> String {
+        self.qualify(&self.version_table_name())
+    }
+
+    /// Quoted, schema-qualified object named after the table, e.g. a trigger
+    /// function
+    pub fn related(&self, suffix: &str) -> String {
+        self.qualify(&format!("{}_{}", self.table_name, suffix))
+    }
+
+    /// Quoted index name (indexes live in their table's schema)
+    pub fn index(&self, suffix: &str) -> String {
+        quote_ident(&format!("idx_{}_{}", self.table_name, suffix))
+    }
+
+    /// NOTIFY channel prefix
+    pub fn channel_prefix(&self) -> &str {
+        &self.channel_prefix
+    }
+
+    /// NOTIFY channel for a session
+    pub fn channel(&self, session_id: &str) -> String {
+        format!("{}{}", self.channel_prefix, session_id)
+    }
+
+    fn qualify(&self, name: &str) -> String {
+        match &self.schema {
+            Some(schema) => format!("{}.{}", quote_ident(schema), quote_ident(name)),
+            None => quote_ident(name),
+        }
+    }
+}
+
+/// Quote an SQL identifier, doubling embedded quotes
+pub fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Quote an SQL string literal, doubling embedded quotes
+pub fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_names() {
+        let names = TableNames::default();
+        assert_eq!(names.table(), "\"solid_mcp_messages\"");
+        assert_eq!(
+            names.index("on_session_and_id"),
+            "\"idx_solid_mcp_messages_on_session_and_id\""
+        );
+        assert_eq!(names.channel("abc"), "solid_mcp_abc");
+    }
+
+    #[test]
+    fn test_configured_names_are_quoted() {
+        let mut config = Config::new("postgres://localhost/app");
+        config.table_name = "tenant\"a".to_string();
+        config.schema = Some("app".to_string());
+        config.channel_prefix = "tenant_a_".to_string();
+
+        let names = TableNames::from_config(&config, true);
+        assert_eq!(names.table(), "\"app\".\"tenant\"\"a\"");
+        assert_eq!(
+            names.version_table(),
+            "\"app\".\"tenant\"\"a_schema_migrations\""
+        );
+        assert_eq!(names.channel("abc"), "tenant_a_abc");
+
+        let names = TableNames::from_config(&config, false);
+        assert_eq!(names.table(), "\"tenant\"\"a\"");
+        assert_eq!(quote_literal("it's"), "'it''s'");
+    }
+}
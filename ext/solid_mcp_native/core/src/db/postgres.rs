@@ -2,10 +2,15 @@
 //!
 //! Supports LISTEN/NOTIFY for real-time message delivery without polling.
 
-use crate::{Message, Result};
+use super::{DatabaseUrl, TableNames};
+use crate::message::NULL_DATA;
+use crate::metrics::DbStats;
+use crate::{Config, Error, Message, Priority, Result};
 use async_trait::async_trait;
-use sqlx::postgres::{PgConnectOptions, PgListener, PgPoolOptions};
-use sqlx::{Pool, Postgres};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::postgres::{PgConnectOptions, PgListener, PgPoolOptions, PgRow};
+use sqlx::{Column, Pool, Postgres, Row, TypeInfo};
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -14,40 +19,95 @@ use std::time::Duration;
 pub struct PostgresPool {
     pool: Pool<Postgres>,
     database_url: String,
+    traceparent: bool,
+    names: TableNames,
 }
 
 impl PostgresPool {
     /// Create a new PostgreSQL pool from a database URL
     ///
-    /// The database and tables must already exist (created by Ruby migrations).
+    /// The tables must already exist, created by the Ruby migration or
+    /// [`Migrator`](super::Migrator).
     pub async fn new(database_url: &str) -> Result<Self> {
-        let options = PgConnectOptions::from_str(database_url)?;
+        Self::connect(
+            &DatabaseUrl::parse(database_url)?,
+            &Config::new(database_url),
+        )
+        .await
+    }
+
+    /// Create a new PostgreSQL pool from a parsed database URL and pool settings
+    ///
+    /// `config.max_connections` overrides the URL's `pool_size` option.
+    pub async fn connect(url: &DatabaseUrl, config: &Config) -> Result<Self> {
+        if !url.is_postgres() {
+            return Err(Error::Config("not a PostgreSQL database URL".to_string()));
+        }
+        let options = PgConnectOptions::from_str(url.connect_url())?
+            .statement_cache_capacity(config.statement_cache_size);
 
         let pool = PgPoolOptions::new()
-            .max_connections(10)
-            .acquire_timeout(Duration::from_secs(30))
+            .max_connections(config.max_connections.or(url.pool_size).unwrap_or(10))
+            .min_connections(config.min_connections)
+            .acquire_timeout(config.acquire_timeout)
+            .idle_timeout(config.idle_timeout)
+            .max_lifetime(config.max_lifetime)
             .connect_with(options)
             .await?;
 
         Ok(Self {
             pool,
-            database_url: database_url.to_string(),
+            database_url: url.connect_url().to_string(),
+            traceparent: false,
+            names: TableNames::from_config(config, true),
         })
     }
 
+    /// Table and channel names in use
+    pub fn names(&self) -> &TableNames {
+        &self.names
+    }
+
+    /// Read and write the `traceparent` column
+    pub fn with_traceparent(mut self, enabled: bool) -> Self {
+        self.traceparent = enabled;
+        self
+    }
+
+    /// Run a trivial query to check the database is reachable
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Snapshot connection pool utilisation
+    pub fn pool_stats(&self) -> DbStats {
+        DbStats {
+            backend: "postgres",
+            size: self.pool.size(),
+            idle: self.pool.num_idle(),
+            max_connections: self.pool.options().get_max_connections(),
+        }
+    }
+
+    /// The underlying pool, for schema changes
+    pub(crate) fn pool(&self) -> &Pool<Postgres> {
+        &self.pool
+    }
+
     /// Create a LISTEN connection for a session
     ///
     /// This is used for real-time message delivery without polling.
     pub async fn listen(&self, session_id: &str) -> Result<PgListener> {
         let mut listener = PgListener::connect(&self.database_url).await?;
-        let channel = format!("solid_mcp_{}", session_id);
+        let channel = self.names.channel(session_id);
         listener.listen(&channel).await?;
         Ok(listener)
     }
 
     /// Send a NOTIFY for a session (called after insert for immediate delivery)
     pub async fn notify(&self, session_id: &str, message_id: i64) -> Result<()> {
-        let channel = format!("solid_mcp_{}", session_id);
+        let channel = self.names.channel(session_id);
         sqlx::query("SELECT pg_notify($1, $2)")
             .bind(&channel)
             .bind(message_id.to_string())
@@ -79,44 +139,37 @@ impl super::Database for PostgresPool {
         after_id: i64,
         limit: i64,
     ) -> Result<Vec<Message>> {
-        let rows = sqlx::query_as::<
-            _,
-            (
-                i64,
-                String,
-                String,
-                String,
-                chrono::DateTime<chrono::Utc>,
-                Option<chrono::DateTime<chrono::Utc>>,
-            ),
-        >(
+        let query = format!(
             r#"
-            SELECT id, session_id, event_type, data, created_at, delivered_at
-            FROM solid_mcp_messages
+            SELECT id, session_id, event_type, data, created_at, delivered_at, {}
+            FROM {}
             WHERE session_id = $1 AND delivered_at IS NULL AND id > $2
             ORDER BY id
             LIMIT $3
             "#,
-        )
-        .bind(session_id)
-        .bind(after_id)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
+            if self.traceparent {
+                "traceparent"
+            } else {
+                "NULL::text AS traceparent"
+            },
+            self.names.table()
+        );
 
-        let messages = rows
-            .into_iter()
-            .map(
-                |(id, session_id, event_type, data, created_at, delivered_at)| Message {
-                    id,
-                    session_id,
-                    event_type,
-                    data,
-                    created_at,
-                    delivered_at,
-                },
-            )
-            .collect();
+        let rows = sqlx::query(&query)
+            .bind(session_id)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let rows = rows
+            .iter()
+            .map(|row| Ok((row.try_get("id")?, decode_row(row))))
+            .collect::<std::result::Result<Vec<_>, sqlx::Error>>()?;
+        let (messages, quarantined) = super::quarantine(session_id, rows);
+        if !quarantined.is_empty() {
+            super::Database::mark_delivered(self, &quarantined).await?;
+        }
 
         Ok(messages)
     }
@@ -126,13 +179,14 @@ impl super::Database for PostgresPool {
             return Ok(());
         }
 
-        sqlx::query(
+        sqlx::query(&format!(
             r#"
-            UPDATE solid_mcp_messages
+            UPDATE {}
             SET delivered_at = NOW()
             WHERE id = ANY($1)
             "#,
-        )
+            self.names.table()
+        ))
         .bind(ids)
         .execute(&self.pool)
         .await?;
@@ -143,12 +197,13 @@ impl super::Database for PostgresPool {
     async fn cleanup_delivered(&self, older_than: Duration) -> Result<u64> {
         let cutoff = chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap();
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
-            DELETE FROM solid_mcp_messages
+            DELETE FROM {}
             WHERE delivered_at IS NOT NULL AND delivered_at < $1
             "#,
-        )
+            self.names.table()
+        ))
         .bind(cutoff)
         .execute(&self.pool)
         .await?;
@@ -159,12 +214,13 @@ impl super::Database for PostgresPool {
     async fn cleanup_undelivered(&self, older_than: Duration) -> Result<u64> {
         let cutoff = chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap();
 
-        let result = sqlx::query(
+        let result = sqlx::query(&format!(
             r#"
-            DELETE FROM solid_mcp_messages
+            DELETE FROM {}
             WHERE delivered_at IS NULL AND created_at < $1
             "#,
-        )
+            self.names.table()
+        ))
         .bind(cutoff)
         .execute(&self.pool)
         .await?;
@@ -173,34 +229,84 @@ impl super::Database for PostgresPool {
     }
 
     async fn max_id(&self) -> Result<i64> {
-        let row: (Option<i64>,) = sqlx::query_as("SELECT MAX(id) FROM solid_mcp_messages")
-            .fetch_one(&self.pool)
-            .await?;
+        let row: (Option<i64>,) =
+            sqlx::query_as(&format!("SELECT MAX(id) FROM {}", self.names.table()))
+                .fetch_one(&self.pool)
+                .await?;
 
         Ok(row.0.unwrap_or(0))
     }
 }
 
+/// Decode one `fetch_after` row
+fn decode_row(row: &PgRow) -> std::result::Result<Message, sqlx::Error> {
+    let data: Option<String> = row.try_get("data")?;
+
+    Ok(Message {
+        id: row.try_get("id")?,
+        session_id: row.try_get("session_id")?,
+        event_type: row.try_get("event_type")?,
+        data: data.unwrap_or_else(|| NULL_DATA.to_string()),
+        created_at: decode_timestamp(row, "created_at")?
+            .ok_or_else(|| sqlx::Error::Decode("created_at is NULL".into()))?,
+        delivered_at: decode_timestamp(row, "delivered_at")?,
+        priority: Priority::Normal,
+        coalesce_key: None,
+        traceparent: row.try_get("traceparent")?,
+    })
+}
+
+/// Decode a `timestamptz` or `timestamp` column
+///
+/// Rails' `t.datetime` creates `timestamp without time zone` and stores UTC.
+/// sqlx connects with `TimeZone=UTC`, so values bound as `timestamptz` are
+/// written and compared in UTC as well.
+fn decode_timestamp(
+    row: &PgRow,
+    column: &str,
+) -> std::result::Result<Option<DateTime<Utc>>, sqlx::Error> {
+    if row.try_column(column)?.type_info().name() == "TIMESTAMP" {
+        let value: Option<NaiveDateTime> = row.try_get(column)?;
+        Ok(value.map(|value| value.and_utc()))
+    } else {
+        row.try_get(column)
+    }
+}
+
 impl PostgresPool {
-    /// Insert using multi-row VALUES (good for small batches)
+    /// Insert with a multi-row VALUES list and NOTIFY each session's channel
+    ///
+    /// The notifications are sent on commit, so listeners don't depend on a
+    /// database trigger to be woken.
     async fn insert_batch_values(&self, messages: &[Message]) -> Result<()> {
-        let mut query = String::from(
-            "INSERT INTO solid_mcp_messages (session_id, event_type, data, created_at) VALUES ",
-        );
+        let (mut query, width) = if self.traceparent {
+            (
+                format!(
+                    "INSERT INTO {} (session_id, event_type, data, created_at, traceparent) VALUES ",
+                    self.names.table()
+                ),
+                5,
+            )
+        } else {
+            (
+                format!(
+                    "INSERT INTO {} (session_id, event_type, data, created_at) VALUES ",
+                    self.names.table()
+                ),
+                4,
+            )
+        };
 
         for (i, _) in messages.iter().enumerate() {
             if i > 0 {
                 query.push_str(", ");
             }
-            let base = i * 4 + 1;
-            query.push_str(&format!(
-                "(${}, ${}, ${}, ${})",
-                base,
-                base + 1,
-                base + 2,
-                base + 3
-            ));
+            let base = i * width + 1;
+            let placeholders: Vec<String> =
+                (base..base + width).map(|n| format!("${}", n)).collect();
+            query.push_str(&format!("({})", placeholders.join(", ")));
         }
+        query.push_str(" RETURNING session_id, id");
 
         let mut q = sqlx::query(&query);
         for msg in messages {
@@ -209,9 +315,34 @@ impl PostgresPool {
                 .bind(&msg.event_type)
                 .bind(&msg.data)
                 .bind(msg.created_at);
+            if self.traceparent {
+                q = q.bind(&msg.traceparent);
+            }
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let inserted: Vec<(String, i64)> = q
+            .try_map(|row: PgRow| Ok((row.try_get(0)?, row.try_get(1)?)))
+            .fetch_all(&mut *tx)
+            .await?;
+
+        // One notification per session, carrying its highest new ID
+        let mut latest: HashMap<String, i64> = HashMap::new();
+        for (session_id, id) in inserted {
+            let entry = latest.entry(session_id).or_insert(id);
+            *entry = (*entry).max(id);
         }
-        q.execute(&self.pool).await?;
+        let (channels, payloads): (Vec<String>, Vec<String>) = latest
+            .into_iter()
+            .map(|(session_id, id)| (self.names.channel(&session_id), id.to_string()))
+            .unzip();
+        sqlx::query("SELECT pg_notify(c, p) FROM unnest($1::text[], $2::text[]) AS t(c, p)")
+            .bind(&channels)
+            .bind(&payloads)
+            .execute(&mut *tx)
+            .await?;
 
+        tx.commit().await?;
         Ok(())
     }
 
@@ -239,4 +370,48 @@ mod tests {
         let pool = PostgresPool::new(&url).await.unwrap();
         let _ = pool.max_id().await.unwrap();
     }
+
+    #[tokio::test]
+    #[ignore] // Requires PostgreSQL
+    async fn test_timestamp_without_time_zone() {
+        let url = std::env::var("DATABASE_URL")
+            .unwrap_or_else(|_| "postgres://localhost/test_solid_mcp".to_string());
+        let config =
+            Config::new(&url).table_name(format!("solid_mcp_naive_{}", std::process::id()));
+        let pool = PostgresPool::connect(&DatabaseUrl::parse(&url).unwrap(), &config)
+            .await
+            .unwrap();
+        let table = pool.names().table();
+
+        // As created by the install generator's `t.datetime` columns
+        sqlx::query(&format!(
+            "CREATE TABLE {table} (id bigserial PRIMARY KEY, \
+             session_id varchar(36) NOT NULL, event_type varchar(50) NOT NULL, data text, \
+             created_at timestamp(6) NOT NULL, delivered_at timestamp(6))"
+        ))
+        .execute(&pool.pool)
+        .await
+        .unwrap();
+
+        let message = Message::new("session-1", "message", "{}");
+        let created_at = message.created_at;
+        pool.insert_batch(&[message]).await.unwrap();
+
+        let fetched = pool.fetch_after("session-1", 0, 10).await.unwrap();
+        let drift = (fetched[0].created_at - created_at)
+            .num_microseconds()
+            .unwrap();
+        assert!(drift.abs() <= 1, "{}", drift);
+        assert_eq!(
+            pool.cleanup_undelivered(Duration::from_secs(3600))
+                .await
+                .unwrap(),
+            0
+        );
+
+        sqlx::query(&format!("DROP TABLE {table}"))
+            .execute(&pool.pool)
+            .await
+            .unwrap();
+    }
 }
@@ -0,0 +1,270 @@
+//! Schema compatibility check
+//!
+//! [`check`] introspects the messages table (`PRAGMA table_info` on SQLite,
+//! `information_schema` on PostgreSQL) and reports every missing or
+//! mismatched column and index in one [`Error::Config`], so a bad schema
+//! fails at startup instead of as a decode error inside a subscriber loop.
+
+use super::DbPool;
+use crate::{Error, Result};
+
+/// A column the backends read or write
+struct Column {
+    name: &'static str,
+    /// Required nullability, or `None` if either is fine
+    nullable: Option<bool>,
+    /// Accepted PostgreSQL `data_type`s (SQLite columns are untyped)
+    pg_types: &'static [&'static str],
+}
+
+const TEXT: &[&str] = &["text", "character varying"];
+/// `t.datetime` creates `timestamp without time zone`, which holds UTC
+const TIMESTAMP: &[&str] = &["timestamp with time zone", "timestamp without time zone"];
+
+const COLUMNS: &[Column] = &[
+    Column {
+        name: "id",
+        // SQLite reports INTEGER PRIMARY KEY columns as nullable
+        nullable: None,
+        pg_types: &["bigint", "integer"],
+    },
+    Column {
+        name: "session_id",
+        nullable: Some(false),
+        pg_types: TEXT,
+    },
+    Column {
+        name: "event_type",
+        nullable: Some(false),
+        pg_types: TEXT,
+    },
+    Column {
+        name: "data",
+        // The Rails migration allows NULL; it decodes as `NULL_DATA`
+        nullable: None,
+        pg_types: TEXT,
+    },
+    Column {
+        name: "created_at",
+        nullable: Some(false),
+        pg_types: TIMESTAMP,
+    },
+    Column {
+        name: "delivered_at",
+        nullable: Some(true),
+        pg_types: TIMESTAMP,
+    },
+];
+
+const TRACEPARENT: Column = Column {
+    name: "traceparent",
+    nullable: Some(true),
+    pg_types: TEXT,
+};
+
+/// Leading columns of the indexes the queries rely on
+const INDEXES: &[&[&str]] = &[&["session_id", "id"], &["delivered_at", "created_at"]];
+
+/// A column as found in the database
+struct ColumnInfo {
+    name: String,
+    data_type: String,
+    nullable: bool,
+}
+
+/// Check that the messages table matches what the backends expect
+///
+/// With `traceparent`, the nullable `traceparent` column is required too.
+/// Extra columns and indexes are ignored.
+pub async fn check(db: &DbPool, traceparent: bool) -> Result<()> {
+    let table = db.names().table();
+    let (columns, indexes) = introspect(db).await?;
+    if columns.is_empty() {
+        return Err(Error::Config(format!("table {} does not exist", table)));
+    }
+
+    let mut problems = Vec::new();
+    let expected = COLUMNS.iter().chain(traceparent.then_some(&TRACEPARENT));
+    for column in expected {
+        let Some(found) = columns.iter().find(|c| c.name == column.name) else {
+            problems.push(format!("missing column {}", column.name));
+            continue;
+        };
+        if db.is_postgres() && !column.pg_types.contains(&found.data_type.as_str()) {
+            problems.push(format!(
+                "column {} is {}, expected {}",
+                column.name,
+                found.data_type,
+                column.pg_types.join(" or ")
+            ));
+        }
+        match column.nullable {
+            Some(false) if found.nullable => {
+                problems.push(format!("column {} must be NOT NULL", column.name));
+            }
+            Some(true) if !found.nullable => {
+                problems.push(format!("column {} must be nullable", column.name));
+            }
+            _ => {}
+        }
+    }
+
+    for required in INDEXES {
+        let covered = indexes
+            .iter()
+            .any(|index| index.len() >= required.len() && index[..required.len()] == **required);
+        if !covered {
+            problems.push(format!("missing index on ({})", required.join(", ")));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "{} does not match the expected schema: {}",
+            table,
+            problems.join("; ")
+        )))
+    }
+}
+
+/// Columns of the messages table and the column lists of its indexes
+async fn introspect(db: &DbPool) -> Result<(Vec<ColumnInfo>, Vec<Vec<String>>)> {
+    match db {
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(pool) => {
+            let table = pool.names().table_name();
+            let pool = pool.writer_pool();
+            let columns: Vec<(String, String, bool)> =
+                sqlx::query_as("SELECT name, type, \"notnull\" = 0 FROM pragma_table_info($1)")
+                    .bind(table)
+                    .fetch_all(pool)
+                    .await?;
+            let index_columns: Vec<(String, String)> = sqlx::query_as(
+                r#"
+                SELECT il.name, ii.name
+                FROM pragma_index_list($1) AS il
+                JOIN pragma_index_info(il.name) AS ii
+                ORDER BY il.name, ii.seqno
+                "#,
+            )
+            .bind(table)
+            .fetch_all(pool)
+            .await?;
+
+            let columns = columns
+                .into_iter()
+                .map(|(name, data_type, nullable)| ColumnInfo {
+                    name,
+                    data_type,
+                    nullable,
+                })
+                .collect();
+            Ok((columns, group_indexes(index_columns)))
+        }
+        #[cfg(feature = "postgres")]
+        DbPool::Postgres(pool) => {
+            let names = pool.names();
+            let pool = pool.pool();
+            let columns: Vec<(String, String, String)> = sqlx::query_as(
+                r#"
+                SELECT column_name::text, data_type::text, is_nullable::text
+                FROM information_schema.columns
+                WHERE table_schema = COALESCE($1, current_schema()) AND table_name = $2
+                "#,
+            )
+            .bind(names.schema())
+            .bind(names.table_name())
+            .fetch_all(pool)
+            .await?;
+            if columns.is_empty() {
+                return Ok((Vec::new(), Vec::new()));
+            }
+            let indexes: Vec<(Vec<String>,)> = sqlx::query_as(
+                r#"
+                SELECT array_agg(a.attname::text ORDER BY k.ord)
+                FROM pg_index x
+                CROSS JOIN LATERAL unnest(x.indkey) WITH ORDINALITY AS k(attnum, ord)
+                JOIN pg_attribute a ON a.attrelid = x.indrelid AND a.attnum = k.attnum
+                WHERE x.indrelid = $1::regclass
+                GROUP BY x.indexrelid
+                "#,
+            )
+            .bind(names.table())
+            .fetch_all(pool)
+            .await?;
+
+            let columns = columns
+                .into_iter()
+                .map(|(name, data_type, is_nullable)| ColumnInfo {
+                    name,
+                    data_type,
+                    nullable: is_nullable == "YES",
+                })
+                .collect();
+            Ok((columns, indexes.into_iter().map(|(c,)| c).collect()))
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn group_indexes(rows: Vec<(String, String)>) -> Vec<Vec<String>> {
+    let mut indexes: Vec<(String, Vec<String>)> = Vec::new();
+    for (index, column) in rows {
+        match indexes.last_mut() {
+            Some((name, columns)) if *name == index => columns.push(column),
+            _ => indexes.push((index, vec![column])),
+        }
+    }
+    indexes.into_iter().map(|(_, columns)| columns).collect()
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::sqlite::SqlitePool;
+
+    async fn memory_db() -> (DbPool, SqlitePool) {
+        let pool = SqlitePool::new("sqlite::memory:").await.unwrap();
+        (DbPool::Sqlite(pool.clone()), pool)
+    }
+
+    #[tokio::test]
+    async fn test_migrated_schema_passes() {
+        let (db, pool) = memory_db().await;
+        pool.setup_test_schema().await.unwrap();
+        check(&db, true).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reports_every_mismatch() {
+        let (db, pool) = memory_db().await;
+        let err = check(&db, false).await.unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        // Rails layout without the cleanup index
+        sqlx::query(
+            "CREATE TABLE solid_mcp_messages (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             session_id VARCHAR(36) NOT NULL, event_type VARCHAR(50) NOT NULL, data TEXT, \
+             created_at DATETIME NOT NULL, delivered_at DATETIME)",
+        )
+        .execute(pool.writer_pool())
+        .await
+        .unwrap();
+        sqlx::query("CREATE INDEX idx_session ON solid_mcp_messages(session_id, id)")
+            .execute(pool.writer_pool())
+            .await
+            .unwrap();
+
+        let err = check(&db, true).await.unwrap_err().to_string();
+        assert!(!err.contains("column data"), "{}", err);
+        assert!(err.contains("missing column traceparent"), "{}", err);
+        assert!(
+            err.contains("missing index on (delivered_at, created_at)"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("session_id"), "{}", err);
+    }
+}
@@ -1,77 +1,248 @@
 //! SQLite database backend for solid-mcp-core
 
-use crate::{Message, Result};
+use super::{BackendKind, DatabaseUrl, SqliteMode, TableNames};
+use crate::config::{JournalMode, SynchronousMode, TimestampFormat};
+use crate::message::NULL_DATA;
+use crate::metrics::DbStats;
+use crate::{Config, Error, Message, Priority, Result};
 use async_trait::async_trait;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{Pool, Sqlite};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Pool, Row, Sqlite};
 use std::str::FromStr;
 use std::time::Duration;
 
-/// SQLite connection pool
+/// SQLite connection pools
+///
+/// Writes go through a single-writer pool. In WAL mode reads use a separate
+/// read-only pool so subscribers don't queue behind the writer.
 #[derive(Clone)]
 pub struct SqlitePool {
-    pool: Pool<Sqlite>,
+    writer: Pool<Sqlite>,
+    reader: Pool<Sqlite>,
+    separate_reader: bool,
+    traceparent: bool,
+    names: TableNames,
+    timestamp_format: TimestampFormat,
 }
 
 impl SqlitePool {
     /// Create a new SQLite pool from a database URL
     ///
-    /// The database and tables must already exist (created by Ruby migrations).
+    /// The tables must already exist, created by the Ruby migration or
+    /// [`Migrator`](super::Migrator).
     pub async fn new(database_url: &str) -> Result<Self> {
-        // Parse the URL and configure for WAL mode (better concurrency)
-        let options = SqliteConnectOptions::from_str(database_url)?
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
-            .synchronous(sqlx::sqlite::SqliteSynchronous::Normal)
-            .busy_timeout(Duration::from_secs(30));
-
-        let pool = SqlitePoolOptions::new()
-            .max_connections(1) // SQLite works best with single writer
-            .connect_with(options)
-            .await?;
+        Self::connect(
+            &DatabaseUrl::parse(database_url)?,
+            &Config::new(database_url),
+        )
+        .await
+    }
 
-        Ok(Self { pool })
+    /// Create a new SQLite pool from a parsed database URL and pool settings
+    ///
+    /// The writer pool always has one connection. `config.max_connections`,
+    /// or else the URL's `pool_size`, sizes the read pool instead. The URL's
+    /// `busy_timeout` overrides `config`.
+    pub async fn connect(url: &DatabaseUrl, config: &Config) -> Result<Self> {
+        let BackendKind::Sqlite {
+            ref path,
+            mode,
+            busy_timeout,
+        } = url.kind
+        else {
+            return Err(Error::Config("not a SQLite database URL".to_string()));
+        };
+
+        let base = SqliteConnectOptions::from_str(url.connect_url())?
+            .busy_timeout(busy_timeout.unwrap_or(config.sqlite_busy_timeout))
+            .statement_cache_capacity(config.statement_cache_size);
+        let writer_options = base
+            .clone()
+            .journal_mode(journal_mode(config.sqlite_journal_mode))
+            .synchronous(synchronous(config.sqlite_synchronous));
+
+        // Always a single writer: more connections only contend for the
+        // database lock, and each in-memory connection is its own database
+        let writer = pool_options(config, 1).connect_with(writer_options).await?;
+
+        // Each connection to an in-memory database gets its own database, and
+        // outside WAL mode readers would block the writer anyway
+        let in_memory = path == ":memory:" || mode == Some(SqliteMode::Memory);
+        let separate_reader = config.sqlite_journal_mode == JournalMode::Wal && !in_memory;
+
+        let reader = if separate_reader {
+            let size = config
+                .max_connections
+                .or(url.pool_size)
+                .unwrap_or(config.sqlite_read_connections);
+            pool_options(config, size)
+                .connect_with(base.read_only(true))
+                .await?
+        } else {
+            writer.clone()
+        };
+
+        Ok(Self {
+            writer,
+            reader,
+            separate_reader,
+            traceparent: false,
+            names: TableNames::from_config(config, false),
+            timestamp_format: config.sqlite_timestamp_format,
+        })
     }
 
-    /// Create tables for testing purposes only
-    #[cfg(test)]
-    pub(crate) async fn setup_test_schema(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS solid_mcp_messages (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                session_id TEXT NOT NULL,
-                event_type TEXT NOT NULL,
-                data TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                delivered_at TEXT
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await?;
+    /// Table and channel names in use
+    pub fn names(&self) -> &TableNames {
+        &self.names
+    }
 
-        sqlx::query(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_solid_mcp_messages_session_id
-            ON solid_mcp_messages(session_id, id)
-            "#,
+    /// Read and write the `traceparent` column
+    pub fn with_traceparent(mut self, enabled: bool) -> Self {
+        self.traceparent = enabled;
+        self
+    }
+
+    /// Run a trivial query to check the database is reachable
+    pub async fn ping(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.writer).await?;
+        Ok(())
+    }
+
+    /// Snapshot connection pool utilisation across the writer and reader pools
+    pub fn pool_stats(&self) -> DbStats {
+        let mut stats = DbStats {
+            backend: "sqlite",
+            size: self.writer.size(),
+            idle: self.writer.num_idle(),
+            max_connections: self.writer.options().get_max_connections(),
+        };
+        if self.separate_reader {
+            stats.size += self.reader.size();
+            stats.idle += self.reader.num_idle();
+            stats.max_connections += self.reader.options().get_max_connections();
+        }
+        stats
+    }
+
+    /// Cleanup cutoffs for rows older than `older_than`, in ActiveRecord and
+    /// RFC 3339 format
+    fn cutoffs(older_than: Duration) -> (String, String) {
+        let cutoff = Utc::now() - chrono::Duration::from_std(older_than).unwrap();
+        (
+            format_timestamp(cutoff, TimestampFormat::ActiveRecord),
+            format_timestamp(cutoff, TimestampFormat::Rfc3339),
         )
-        .execute(&self.pool)
-        .await?;
+    }
 
-        sqlx::query(
+    /// `DELETE` of rows matching `filter` whose `column` is older than the
+    /// cutoffs bound as `$1` (ActiveRecord) and `$2` (RFC 3339)
+    ///
+    /// Both formats sort correctly among themselves but not against each
+    /// other: on the same day `' '` sorts before `'T'`. The range on `$2`,
+    /// the later of the two strings, keeps the predicate indexable; rows in
+    /// ActiveRecord format must also be older than `$1`. Values in any other
+    /// format fall back to `julianday()`.
+    fn cleanup_sql(&self, filter: &str, column: &str) -> String {
+        format!(
             r#"
-            CREATE INDEX IF NOT EXISTS idx_solid_mcp_messages_delivered
-            ON solid_mcp_messages(delivered_at, created_at)
+            DELETE FROM {table}
+            WHERE {filter} AND {column} < $2
+              AND ({column} < $1
+                   OR substr({column}, 11, 1) = 'T'
+                   OR (substr({column}, 11, 1) NOT IN (' ', 'T')
+                       AND julianday({column}) < julianday($1)))
             "#,
+            table = self.names.table(),
         )
-        .execute(&self.pool)
-        .await?;
+    }
+
+    /// The writer pool, for schema changes
+    pub(crate) fn writer_pool(&self) -> &Pool<Sqlite> {
+        &self.writer
+    }
 
+    /// Create tables for testing purposes only
+    #[cfg(test)]
+    pub(crate) async fn setup_test_schema(&self) -> Result<()> {
+        super::migrate::run_sqlite(&self.writer, &self.names).await?;
         Ok(())
     }
 }
 
+/// Decode one `fetch_after` row
+fn decode_row(row: &SqliteRow) -> std::result::Result<Message, sqlx::Error> {
+    let created_at: String = row.try_get("created_at")?;
+    let delivered_at: Option<String> = row.try_get("delivered_at")?;
+    let data: Option<String> = row.try_get("data")?;
+
+    Ok(Message {
+        id: row.try_get("id")?,
+        session_id: row.try_get("session_id")?,
+        event_type: row.try_get("event_type")?,
+        data: data.unwrap_or_else(|| NULL_DATA.to_string()),
+        created_at: parse_timestamp(&created_at).ok_or_else(|| {
+            sqlx::Error::Decode(format!("invalid created_at {:?}", created_at).into())
+        })?,
+        delivered_at: delivered_at.as_deref().and_then(parse_timestamp),
+        priority: Priority::Normal,
+        coalesce_key: None,
+        traceparent: row.try_get("traceparent")?,
+    })
+}
+
+/// ActiveRecord's SQLite datetime format
+const ACTIVE_RECORD_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.6f";
+
+/// Format a timestamp for storage
+fn format_timestamp(time: DateTime<Utc>, format: TimestampFormat) -> String {
+    match format {
+        TimestampFormat::ActiveRecord => time.format(ACTIVE_RECORD_FORMAT).to_string(),
+        TimestampFormat::Rfc3339 => time.to_rfc3339(),
+    }
+}
+
+/// Parse an RFC 3339 or ActiveRecord (UTC, optional fraction) timestamp
+fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+fn pool_options(config: &Config, max_connections: u32) -> SqlitePoolOptions {
+    SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .min_connections(config.min_connections.min(max_connections))
+        .acquire_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout)
+        .max_lifetime(config.max_lifetime)
+}
+
+fn journal_mode(mode: JournalMode) -> SqliteJournalMode {
+    match mode {
+        JournalMode::Delete => SqliteJournalMode::Delete,
+        JournalMode::Truncate => SqliteJournalMode::Truncate,
+        JournalMode::Persist => SqliteJournalMode::Persist,
+        JournalMode::Memory => SqliteJournalMode::Memory,
+        JournalMode::Wal => SqliteJournalMode::Wal,
+        JournalMode::Off => SqliteJournalMode::Off,
+    }
+}
+
+fn synchronous(mode: SynchronousMode) -> SqliteSynchronous {
+    match mode {
+        SynchronousMode::Off => SqliteSynchronous::Off,
+        SynchronousMode::Normal => SqliteSynchronous::Normal,
+        SynchronousMode::Full => SqliteSynchronous::Full,
+        SynchronousMode::Extra => SqliteSynchronous::Extra,
+    }
+}
+
 #[async_trait]
 impl super::Database for SqlitePool {
     async fn insert_batch(&self, messages: &[Message]) -> Result<()> {
@@ -80,28 +251,44 @@ impl super::Database for SqlitePool {
         }
 
         // Build batch insert query
-        let mut query = String::from(
-            "INSERT INTO solid_mcp_messages (session_id, event_type, data, created_at) VALUES ",
-        );
+        let (mut query, width) = if self.traceparent {
+            (
+                format!(
+                    "INSERT INTO {} (session_id, event_type, data, created_at, traceparent) VALUES ",
+                    self.names.table()
+                ),
+                5,
+            )
+        } else {
+            (
+                format!(
+                    "INSERT INTO {} (session_id, event_type, data, created_at) VALUES ",
+                    self.names.table()
+                ),
+                4,
+            )
+        };
 
-        let mut params: Vec<String> = Vec::with_capacity(messages.len() * 4);
+        let mut params: Vec<Option<String>> = Vec::with_capacity(messages.len() * width);
 
         for (i, msg) in messages.iter().enumerate() {
             if i > 0 {
                 query.push_str(", ");
             }
-            let base = i * 4 + 1;
-            query.push_str(&format!(
-                "(${}, ${}, ${}, ${})",
-                base,
-                base + 1,
-                base + 2,
-                base + 3
-            ));
-            params.push(msg.session_id.clone());
-            params.push(msg.event_type.clone());
-            params.push(msg.data.clone());
-            params.push(msg.created_at.to_rfc3339());
+            let base = i * width + 1;
+            let placeholders: Vec<String> =
+                (base..base + width).map(|n| format!("${}", n)).collect();
+            query.push_str(&format!("({})", placeholders.join(", ")));
+            params.push(Some(msg.session_id.clone()));
+            params.push(Some(msg.event_type.clone()));
+            params.push(Some(msg.data.clone()));
+            params.push(Some(format_timestamp(
+                msg.created_at,
+                self.timestamp_format,
+            )));
+            if self.traceparent {
+                params.push(msg.traceparent.clone());
+            }
         }
 
         // Execute with parameters
@@ -109,7 +296,7 @@ impl super::Database for SqlitePool {
         for param in &params {
             q = q.bind(param);
         }
-        q.execute(&self.pool).await?;
+        q.execute(&self.writer).await?;
 
         Ok(())
     }
@@ -120,40 +307,37 @@ impl super::Database for SqlitePool {
         after_id: i64,
         limit: i64,
     ) -> Result<Vec<Message>> {
-        let rows = sqlx::query_as::<_, (i64, String, String, String, String, Option<String>)>(
+        let query = format!(
             r#"
-            SELECT id, session_id, event_type, data, created_at, delivered_at
-            FROM solid_mcp_messages
+            SELECT id, session_id, event_type, data, created_at, delivered_at, {}
+            FROM {}
             WHERE session_id = $1 AND delivered_at IS NULL AND id > $2
             ORDER BY id
             LIMIT $3
             "#,
-        )
-        .bind(session_id)
-        .bind(after_id)
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await?;
-
-        let messages = rows
-            .into_iter()
-            .map(
-                |(id, session_id, event_type, data, created_at, delivered_at)| Message {
-                    id,
-                    session_id,
-                    event_type,
-                    data,
-                    created_at: chrono::DateTime::parse_from_rfc3339(&created_at)
-                        .unwrap_or_default()
-                        .with_timezone(&chrono::Utc),
-                    delivered_at: delivered_at.and_then(|d| {
-                        chrono::DateTime::parse_from_rfc3339(&d)
-                            .ok()
-                            .map(|dt| dt.with_timezone(&chrono::Utc))
-                    }),
-                },
-            )
-            .collect();
+            if self.traceparent {
+                "traceparent"
+            } else {
+                "NULL AS traceparent"
+            },
+            self.names.table()
+        );
+
+        let rows = sqlx::query(&query)
+            .bind(session_id)
+            .bind(after_id)
+            .bind(limit)
+            .fetch_all(&self.reader)
+            .await?;
+
+        let rows = rows
+            .iter()
+            .map(|row| Ok((row.try_get("id")?, decode_row(row))))
+            .collect::<std::result::Result<Vec<_>, sqlx::Error>>()?;
+        let (messages, quarantined) = super::quarantine(session_id, rows);
+        if !quarantined.is_empty() {
+            super::Database::mark_delivered(self, &quarantined).await?;
+        }
 
         Ok(messages)
     }
@@ -166,58 +350,50 @@ impl super::Database for SqlitePool {
         // $1 is for the timestamp, ids start from $2
         let placeholders: Vec<String> = (2..=ids.len() + 1).map(|i| format!("${}", i)).collect();
         let query = format!(
-            "UPDATE solid_mcp_messages SET delivered_at = $1 WHERE id IN ({})",
+            "UPDATE {} SET delivered_at = $1 WHERE id IN ({})",
+            self.names.table(),
             placeholders.join(", ")
         );
 
-        let now = chrono::Utc::now().to_rfc3339();
+        let now = format_timestamp(Utc::now(), self.timestamp_format);
         let mut q = sqlx::query(&query).bind(&now);
         for id in ids {
             q = q.bind(id);
         }
-        q.execute(&self.pool).await?;
+        q.execute(&self.writer).await?;
 
         Ok(())
     }
 
     async fn cleanup_delivered(&self, older_than: Duration) -> Result<u64> {
-        let cutoff =
-            (chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap()).to_rfc3339();
+        let (active_record, rfc3339) = Self::cutoffs(older_than);
 
-        let result = sqlx::query(
-            r#"
-            DELETE FROM solid_mcp_messages
-            WHERE delivered_at IS NOT NULL AND delivered_at < $1
-            "#,
-        )
-        .bind(&cutoff)
-        .execute(&self.pool)
-        .await?;
+        let result = sqlx::query(&self.cleanup_sql("delivered_at IS NOT NULL", "delivered_at"))
+            .bind(&active_record)
+            .bind(&rfc3339)
+            .execute(&self.writer)
+            .await?;
 
         Ok(result.rows_affected())
     }
 
     async fn cleanup_undelivered(&self, older_than: Duration) -> Result<u64> {
-        let cutoff =
-            (chrono::Utc::now() - chrono::Duration::from_std(older_than).unwrap()).to_rfc3339();
+        let (active_record, rfc3339) = Self::cutoffs(older_than);
 
-        let result = sqlx::query(
-            r#"
-            DELETE FROM solid_mcp_messages
-            WHERE delivered_at IS NULL AND created_at < $1
-            "#,
-        )
-        .bind(&cutoff)
-        .execute(&self.pool)
-        .await?;
+        let result = sqlx::query(&self.cleanup_sql("delivered_at IS NULL", "created_at"))
+            .bind(&active_record)
+            .bind(&rfc3339)
+            .execute(&self.writer)
+            .await?;
 
         Ok(result.rows_affected())
     }
 
     async fn max_id(&self) -> Result<i64> {
-        let row: (Option<i64>,) = sqlx::query_as("SELECT MAX(id) FROM solid_mcp_messages")
-            .fetch_one(&self.pool)
-            .await?;
+        let row: (Option<i64>,) =
+            sqlx::query_as(&format!("SELECT MAX(id) FROM {}", self.names.table()))
+                .fetch_one(&self.reader)
+                .await?;
 
         Ok(row.0.unwrap_or(0))
     }
@@ -273,4 +449,257 @@ mod tests {
         let fetched = pool.fetch_after("session-1", 0, 100).await.unwrap();
         assert_eq!(fetched.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_separate_read_pool() {
+        let path = std::env::temp_dir().join(format!("solid_mcp_rw_{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc", path.display());
+        let config = Config::new(&url).sqlite_read_connections(2);
+
+        let pool = SqlitePool::connect(&DatabaseUrl::parse(&url).unwrap(), &config)
+            .await
+            .unwrap();
+        pool.setup_test_schema().await.unwrap();
+        assert!(pool.separate_reader);
+        assert_eq!(pool.pool_stats().max_connections, 3);
+
+        pool.insert_batch(&[Message::new("session-1", "message", "{}")])
+            .await
+            .unwrap();
+        assert_eq!(
+            pool.fetch_after("session-1", 0, 100).await.unwrap().len(),
+            1
+        );
+        assert_eq!(pool.max_id().await.unwrap(), 1);
+
+        // Reader connections reject writes
+        let write = sqlx::query("DELETE FROM solid_mcp_messages")
+            .execute(&pool.reader)
+            .await;
+        assert!(write.is_err());
+
+        pool.writer.close().await;
+        pool.reader.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_size_sizes_reader() {
+        let path = std::env::temp_dir().join(format!("solid_mcp_ps_{}.db", std::process::id()));
+        let url = format!("sqlite://{}?mode=rwc&pool_size=5", path.display());
+
+        let pool = SqlitePool::connect(&DatabaseUrl::parse(&url).unwrap(), &Config::new(&url))
+            .await
+            .unwrap();
+        assert_eq!(pool.writer.options().get_max_connections(), 1);
+        assert_eq!(pool.reader.options().get_max_connections(), 5);
+        pool.writer.close().await;
+        pool.reader.close().await;
+
+        // An explicit max_connections wins over the URL
+        let config = Config::new(&url).max_connections(2);
+        let pool = SqlitePool::connect(&DatabaseUrl::parse(&url).unwrap(), &config)
+            .await
+            .unwrap();
+        assert_eq!(pool.reader.options().get_max_connections(), 2);
+        pool.writer.close().await;
+        pool.reader.close().await;
+
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+
+        // In-memory databases keep a single shared connection
+        let pool = SqlitePool::new("sqlite::memory:?pool_size=5")
+            .await
+            .unwrap();
+        assert_eq!(pool.writer.options().get_max_connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_memory_database_shares_pool() {
+        let pool = create_test_pool().await;
+        assert!(!pool.separate_reader);
+    }
+
+    #[tokio::test]
+    async fn test_null_data_and_quarantine() {
+        let pool = SqlitePool::new("sqlite::memory:").await.unwrap();
+        // Rails layout: data is nullable
+        sqlx::query(
+            "CREATE TABLE solid_mcp_messages (id INTEGER PRIMARY KEY AUTOINCREMENT, \
+             session_id TEXT NOT NULL, event_type TEXT NOT NULL, data TEXT, \
+             created_at TEXT NOT NULL, delivered_at TEXT)",
+        )
+        .execute(&pool.writer)
+        .await
+        .unwrap();
+        let now = chrono::Utc::now().to_rfc3339();
+        for data in ["NULL", "X'FF'", "'{}'"] {
+            sqlx::query(&format!(
+                "INSERT INTO solid_mcp_messages (session_id, event_type, data, created_at) \
+                 VALUES ('session-1', 'message', {}, $1)",
+                data
+            ))
+            .bind(&now)
+            .execute(&pool.writer)
+            .await
+            .unwrap();
+        }
+
+        // The BLOB row is skipped and marked, the others still arrive
+        let fetched = pool.fetch_after("session-1", 0, 100).await.unwrap();
+        let data: Vec<_> = fetched.iter().map(|m| (m.id, m.data.as_str())).collect();
+        assert_eq!(data, vec![(1, NULL_DATA), (3, "{}")]);
+
+        let delivered: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM solid_mcp_messages WHERE delivered_at IS NOT NULL",
+        )
+        .fetch_one(&pool.writer)
+        .await
+        .unwrap();
+        assert_eq!(delivered.0, 1);
+        assert_eq!(
+            pool.fetch_after("session-1", 0, 100).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = "2025-06-24T12:30:45.123456Z"
+            .parse::<DateTime<Utc>>()
+            .unwrap();
+        assert_eq!(
+            parse_timestamp("2025-06-24 12:30:45.123456"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp("2025-06-24T12:30:45.123456+00:00"),
+            Some(expected)
+        );
+        assert_eq!(
+            parse_timestamp("2025-06-24 12:30:45"),
+            Some("2025-06-24T12:30:45Z".parse().unwrap())
+        );
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(
+            format_timestamp(expected, TimestampFormat::ActiveRecord),
+            "2025-06-24 12:30:45.123456"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mixed_writers() {
+        let mut pool = create_test_pool().await;
+        let now = Utc::now();
+        let ruby_row = |created_at: DateTime<Utc>| {
+            let created_at = created_at.format("%Y-%m-%d %H:%M:%S.%6f").to_string();
+            let pool = pool.writer.clone();
+            async move {
+                sqlx::query(
+                    "INSERT INTO solid_mcp_messages (session_id, event_type, data, created_at) \
+                     VALUES ('session-1', 'message', '{}', $1)",
+                )
+                .bind(created_at)
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        };
+
+        // Ruby rows: one stale, one recent
+        ruby_row("2020-01-01T12:00:00.123456Z".parse().unwrap()).await;
+        ruby_row(now - chrono::Duration::minutes(30)).await;
+
+        // Rust rows in both formats, the RFC 3339 one stale
+        let mut stale = Message::new("session-1", "message", "{}");
+        stale.created_at = now - chrono::Duration::hours(2);
+        pool.timestamp_format = TimestampFormat::Rfc3339;
+        pool.insert_batch(&[stale]).await.unwrap();
+        pool.timestamp_format = TimestampFormat::ActiveRecord;
+        pool.insert_batch(&[Message::new("session-1", "message", "{}")])
+            .await
+            .unwrap();
+
+        let fetched = pool.fetch_after("session-1", 0, 100).await.unwrap();
+        assert_eq!(fetched.len(), 4);
+        assert_eq!(
+            fetched[0].created_at,
+            "2020-01-01T12:00:00.123456Z"
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        );
+
+        let stored: (String,) =
+            sqlx::query_as("SELECT created_at FROM solid_mcp_messages WHERE id = 4")
+                .fetch_one(&pool.writer)
+                .await
+                .unwrap();
+        assert!(!stored.0.contains('T'), "{}", stored.0);
+
+        // Cutoffs compare correctly across formats
+        let deleted = pool
+            .cleanup_undelivered(Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(deleted, 2);
+        let remaining: Vec<i64> = pool
+            .fetch_after("session-1", 0, 100)
+            .await
+            .unwrap()
+            .iter()
+            .map(|m| m.id)
+            .collect();
+        assert_eq!(remaining, vec![2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_uses_index() {
+        let pool = create_test_pool().await;
+        for (filter, column) in [
+            ("delivered_at IS NOT NULL", "delivered_at"),
+            ("delivered_at IS NULL", "created_at"),
+        ] {
+            let plan: Vec<(i64, i64, i64, String)> = sqlx::query_as(&format!(
+                "EXPLAIN QUERY PLAN {}",
+                pool.cleanup_sql(filter, column)
+            ))
+            .bind("2025-06-24 12:00:00.000000")
+            .bind("2025-06-24T12:00:00+00:00")
+            .fetch_all(&pool.writer)
+            .await
+            .unwrap();
+            assert!(
+                plan.iter().any(|row| row.3.contains("USING")
+                    && row.3.contains("INDEX")
+                    && row.3.contains(&format!("{}<?", column))),
+                "{:?}",
+                plan
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traceparent_round_trip() {
+        let pool = create_test_pool().await.with_traceparent(true);
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+        let messages = vec![
+            Message::new("session-1", "message", "{}").with_traceparent(traceparent),
+            Message::new("session-1", "message", "{}"),
+        ];
+        pool.insert_batch(&messages).await.unwrap();
+
+        let fetched = pool.fetch_after("session-1", 0, 100).await.unwrap();
+        assert_eq!(fetched[0].traceparent.as_deref(), Some(traceparent));
+        assert_eq!(fetched[1].traceparent, None);
+
+        // Without the flag the column is neither written nor read
+        let pool = pool.with_traceparent(false);
+        let fetched = pool.fetch_after("session-1", 0, 100).await.unwrap();
+        assert_eq!(fetched[0].traceparent, None);
+    }
 }
@@ -10,7 +10,23 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     /// Database operation failed
     #[error("database error: {0}")]
-    Database(#[from] sqlx::Error),
+    Database(#[source] sqlx::Error),
+
+    /// Connection to the database was lost or could not be established
+    #[error("database connection lost: {0}")]
+    ConnectionLost(#[source] sqlx::Error),
+
+    /// Timed out waiting for a connection from the pool
+    #[error("database pool timed out")]
+    PoolTimeout,
+
+    /// Write violated a database constraint
+    #[error("constraint violation: {0}")]
+    Constraint(#[source] sqlx::Error),
+
+    /// Table or column layout does not match what the engine expects
+    #[error("schema mismatch: {0}")]
+    SchemaMismatch(#[source] sqlx::Error),
 
     /// JSON serialization/deserialization failed
     #[error("json error: {0}")]
@@ -24,6 +40,18 @@ pub enum Error {
     #[error("channel receive error: shutdown")]
     ChannelRecv,
 
+    /// Writer queue is full
+    #[error("writer queue full")]
+    QueueFull,
+
+    /// Engine has not been initialized
+    #[error("engine not initialized")]
+    NotInitialized,
+
+    /// Session already has a subscriber
+    #[error("already subscribed to session {0}")]
+    AlreadySubscribed(String),
+
     /// Configuration error
     #[error("configuration error: {0}")]
     Config(String),
@@ -32,7 +60,160 @@ pub enum Error {
     #[error("shutdown requested")]
     Shutdown,
 
+    /// Writer task crashed and is waiting to be restarted
+    #[error("writer unavailable: {0}")]
+    WriterUnavailable(String),
+
     /// Session not found
     #[error("session not found: {0}")]
     SessionNotFound(String),
 }
+
+/// SQLSTATE codes for serialization failures and deadlocks
+const TRANSIENT_SQLSTATES: &[&str] = &["40001", "40P01"];
+
+/// SQLSTATE codes for undefined table/column
+const SCHEMA_SQLSTATES: &[&str] = &["42P01", "42703"];
+
+impl Error {
+    /// Check if the operation may succeed when retried
+    ///
+    /// Connection loss, pool timeouts, a full queue, a restarting writer,
+    /// serialization failures, deadlocks and SQLite busy/locked errors are
+    /// retryable. Constraint and schema errors are not.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::ConnectionLost(_)
+            | Error::PoolTimeout
+            | Error::QueueFull
+            | Error::WriterUnavailable(_) => true,
+            Error::Database(sqlx::Error::Database(db)) => {
+                db.code()
+                    .is_some_and(|code| TRANSIENT_SQLSTATES.contains(&code.as_ref()))
+                    || is_sqlite_busy(db.message())
+            }
+            _ => false,
+        }
+    }
+
+    /// SQLSTATE (or SQLite result) code of the underlying database error
+    pub fn sqlstate(&self) -> Option<String> {
+        self.database_error()
+            .and_then(|db| db.code())
+            .map(|code| code.into_owned())
+    }
+
+    /// Message reported by the database driver
+    pub fn database_message(&self) -> Option<&str> {
+        self.database_error().map(|db| db.message())
+    }
+
+    fn database_error(&self) -> Option<&dyn sqlx::error::DatabaseError> {
+        match self {
+            Error::Database(sqlx::Error::Database(db))
+            | Error::ConnectionLost(sqlx::Error::Database(db))
+            | Error::Constraint(sqlx::Error::Database(db))
+            | Error::SchemaMismatch(sqlx::Error::Database(db)) => Some(db.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        use sqlx::error::ErrorKind;
+
+        match &err {
+            sqlx::Error::Io(_)
+            | sqlx::Error::Tls(_)
+            | sqlx::Error::PoolClosed
+            | sqlx::Error::WorkerCrashed => Error::ConnectionLost(err),
+            sqlx::Error::PoolTimedOut => Error::PoolTimeout,
+            sqlx::Error::ColumnNotFound(_)
+            | sqlx::Error::ColumnDecode { .. }
+            | sqlx::Error::Decode(_)
+            | sqlx::Error::TypeNotFound { .. } => Error::SchemaMismatch(err),
+            sqlx::Error::Database(db) => {
+                let schema = db
+                    .code()
+                    .is_some_and(|code| SCHEMA_SQLSTATES.contains(&code.as_ref()))
+                    || db.message().starts_with("no such table")
+                    || db.message().starts_with("no such column");
+
+                if schema {
+                    Error::SchemaMismatch(err)
+                } else if matches!(db.kind(), ErrorKind::Other) {
+                    Error::Database(err)
+                } else {
+                    Error::Constraint(err)
+                }
+            }
+            _ => Error::Database(err),
+        }
+    }
+}
+
+fn is_sqlite_busy(message: &str) -> bool {
+    message.contains("database is locked") || message.contains("database is busy")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn sqlite_error(sql: &str) -> Error {
+        let pool = SqlitePoolOptions::new()
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query("CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO t (id, name) VALUES (1, 'a')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(sql).execute(&pool).await.unwrap_err().into()
+    }
+
+    #[test]
+    fn test_classify_pool_errors() {
+        assert!(matches!(
+            Error::from(sqlx::Error::PoolTimedOut),
+            Error::PoolTimeout
+        ));
+        assert!(matches!(
+            Error::from(sqlx::Error::PoolClosed),
+            Error::ConnectionLost(_)
+        ));
+        assert!(matches!(
+            Error::from(sqlx::Error::RowNotFound),
+            Error::Database(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_classify_database_errors() {
+        let err = sqlite_error("INSERT INTO t (id, name) VALUES (1, 'b')").await;
+        assert!(matches!(err, Error::Constraint(_)), "{err:?}");
+        assert!(!err.is_retryable());
+        assert!(err.sqlstate().is_some());
+        assert!(err.database_message().unwrap().contains("UNIQUE"));
+
+        let err = sqlite_error("SELECT * FROM missing").await;
+        assert!(matches!(err, Error::SchemaMismatch(_)), "{err:?}");
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(Error::from(sqlx::Error::PoolTimedOut).is_retryable());
+        assert!(Error::from(sqlx::Error::WorkerCrashed).is_retryable());
+        assert!(Error::QueueFull.is_retryable());
+        assert!(!Error::NotInitialized.is_retryable());
+        assert!(!Error::AlreadySubscribed("s".to_string()).is_retryable());
+        assert!(!Error::Shutdown.is_retryable());
+    }
+}
@@ -0,0 +1,110 @@
+//! Health and readiness reporting for solid-mcp-core
+//!
+//! [`PubSub::health`](crate::PubSub::health) assembles a [`Health`] report that
+//! hosts can map onto liveness/readiness probes.
+
+use crate::subscriber::SubscriberState;
+use crate::supervisor::TaskState;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Maximum time to wait for the database ping
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queue fill ratio at or above which the engine reports not ready
+pub const SATURATION_THRESHOLD: f64 = 0.9;
+
+/// Database reachability
+#[derive(Debug, Clone, Serialize)]
+pub struct DbHealth {
+    /// Whether the ping succeeded
+    pub reachable: bool,
+    /// Ping round-trip time
+    pub latency: Option<Duration>,
+    /// Error message if the ping failed or timed out
+    pub error: Option<String>,
+}
+
+/// State of a single subscriber task
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberHealth {
+    /// Session ID
+    pub session_id: String,
+    /// Connection state (LISTEN state on PostgreSQL)
+    pub state: SubscriberState,
+}
+
+/// Health report for the pub/sub engine
+#[derive(Debug, Clone, Serialize)]
+pub struct Health {
+    /// Database reachability
+    pub db: DbHealth,
+    /// Whether the writer task is running (not backing off after a crash)
+    pub writer_alive: bool,
+    /// Lifecycle state of the writer task
+    pub writer_state: TaskState,
+    /// When the writer task last crashed
+    pub writer_last_failure_at: Option<DateTime<Utc>>,
+    /// Fill ratio of the fuller writer queue (0.0 - 1.0)
+    pub queue_saturation: f64,
+    /// Per-session subscriber states
+    pub subscribers: Vec<SubscriberHealth>,
+    /// Subscriber tasks that exited without being stopped
+    pub dead_subscribers: usize,
+}
+
+impl Health {
+    /// Check if the engine can accept and deliver messages
+    ///
+    /// Requires a reachable database, a running writer, queue saturation below
+    /// [`SATURATION_THRESHOLD`] and no dead subscriber tasks.
+    pub fn is_healthy(&self) -> bool {
+        self.db.reachable
+            && self.writer_alive
+            && self.queue_saturation < SATURATION_THRESHOLD
+            && self.dead_subscribers == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy_report() -> Health {
+        Health {
+            db: DbHealth {
+                reachable: true,
+                latency: Some(Duration::from_millis(1)),
+                error: None,
+            },
+            writer_alive: true,
+            writer_state: TaskState::Running,
+            writer_last_failure_at: None,
+            queue_saturation: 0.0,
+            subscribers: Vec::new(),
+            dead_subscribers: 0,
+        }
+    }
+
+    #[test]
+    fn test_is_healthy() {
+        assert!(healthy_report().is_healthy());
+
+        let mut report = healthy_report();
+        report.db.reachable = false;
+        assert!(!report.is_healthy());
+
+        let mut report = healthy_report();
+        report.writer_alive = false;
+        assert!(!report.is_healthy());
+
+        let mut report = healthy_report();
+        report.queue_saturation = SATURATION_THRESHOLD;
+        assert!(!report.is_healthy());
+
+        let mut report = healthy_report();
+        report.dead_subscribers = 1;
+        assert!(!report.is_healthy());
+    }
+}
@@ -0,0 +1,140 @@
+//! Pull-based message delivery
+//!
+//! An [`Inbox`] buffers messages delivered by a subscriber until a caller
+//! pulls them with [`Inbox::recv`]. This suits request loops such as SSE
+//! handlers better than callbacks. The buffer is bounded: when a caller stops
+//! receiving, the oldest messages are dropped and counted.
+
+use crate::Message;
+use crate::subscriber::MessageCallback;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Buffer of messages waiting to be received
+#[derive(Debug)]
+pub struct Inbox {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    queue: Mutex<VecDeque<Message>>,
+    capacity: usize,
+    closed: AtomicBool,
+    notify: Notify,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Shared {
+    fn queue(&self) -> std::sync::MutexGuard<'_, VecDeque<Message>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Closes the inbox when the subscriber drops its callback
+struct Closer(Arc<Shared>);
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        self.0.closed.store(true, Ordering::SeqCst);
+        self.0.notify.notify_one();
+    }
+}
+
+impl Inbox {
+    /// Create an inbox holding up to `capacity` messages and the subscriber
+    /// callback that feeds it
+    ///
+    /// When full, the oldest message is dropped and `dropped` incremented.
+    /// The inbox is closed once the callback is dropped (i.e. the subscriber
+    /// stopped).
+    pub fn new(capacity: usize, dropped: Arc<AtomicU64>) -> (Self, MessageCallback) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            closed: AtomicBool::new(false),
+            notify: Notify::new(),
+            dropped,
+        });
+        let closer = Closer(shared.clone());
+        let callback: MessageCallback = Box::new(move |message| {
+            let shared = &closer.0;
+            let mut queue = shared.queue();
+            if queue.len() >= shared.capacity {
+                queue.pop_front();
+                shared.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            queue.push_back(message);
+            drop(queue);
+            shared.notify.notify_one();
+        });
+        (Self { shared }, callback)
+    }
+
+    /// Wait up to `timeout` for messages
+    ///
+    /// Returns every buffered message as soon as at least one is available,
+    /// an empty batch on timeout, and `None` once the inbox is closed and
+    /// drained.
+    pub async fn recv(&self, timeout: Duration) -> Option<Vec<Message>> {
+        let deadline = tokio::time::Instant::now().checked_add(timeout);
+        loop {
+            let messages: Vec<Message> = self.shared.queue().drain(..).collect();
+            if !messages.is_empty() {
+                return Some(messages);
+            }
+            if self.shared.closed.load(Ordering::SeqCst) {
+                return None;
+            }
+
+            let notified = self.shared.notify.notified();
+            match deadline {
+                Some(deadline) => {
+                    if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                        return Some(Vec::new());
+                    }
+                }
+                None => notified.await,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_inbox_recv() {
+        let (inbox, callback) = Inbox::new(10, Arc::default());
+
+        let empty = inbox.recv(Duration::from_millis(10)).await.unwrap();
+        assert!(empty.is_empty());
+
+        callback(Message::new("session-1", "message", "1"));
+        callback(Message::new("session-1", "message", "2"));
+        let messages = inbox.recv(Duration::from_millis(10)).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].data, "2");
+
+        drop(callback);
+        assert!(inbox.recv(Duration::from_millis(10)).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_inbox_drops_oldest_when_full() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let (inbox, callback) = Inbox::new(2, dropped.clone());
+
+        for i in 0..5 {
+            callback(Message::new("session-1", "message", i.to_string()));
+        }
+        let messages = inbox.recv(Duration::from_millis(10)).await.unwrap();
+        let data: Vec<_> = messages.iter().map(|m| m.data.as_str()).collect();
+        assert_eq!(data, vec!["3", "4"]);
+        assert_eq!(dropped.load(Ordering::Relaxed), 3);
+    }
+}
@@ -10,16 +10,27 @@
 //! ## Features
 //! - `sqlite` - Enable SQLite backend (default)
 //! - `postgres` - Enable PostgreSQL backend with LISTEN/NOTIFY (default)
+//! - `metrics` - Prometheus text exposition and optional HTTP listener
+//! - `opentelemetry` - Parent delivery spans to the publisher's trace via `tracing-opentelemetry`
 
 pub mod config;
 pub mod db;
 pub mod error;
+pub mod health;
+pub mod inbox;
 pub mod message;
+pub mod metrics;
+#[cfg(feature = "metrics")]
+pub mod prometheus;
 pub mod pubsub;
 pub mod subscriber;
+pub mod supervisor;
+pub mod trace_context;
 pub mod writer;
 
-pub use config::Config;
+pub use config::{Config, JournalMode, SynchronousMode, TimestampFormat};
 pub use error::{Error, Result};
-pub use message::Message;
+pub use health::Health;
+pub use message::{Message, Priority};
+pub use metrics::Stats;
 pub use pubsub::PubSub;
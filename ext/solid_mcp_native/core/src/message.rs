@@ -3,6 +3,26 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Delivery priority of a message
+///
+/// High-priority messages (e.g. `connection_closed` or cancellations) skip ahead
+/// of queued normal messages in the writer. Ordering is preserved per session
+/// within the same priority.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    /// Regular traffic (default)
+    #[default]
+    Normal,
+    /// Control events that must not wait behind bulk traffic
+    High,
+}
+
+/// Payload for rows whose `data` column is NULL (JSON `null`)
+pub const NULL_DATA: &str = "null";
+
 /// A message in the pub/sub system
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Message {
@@ -16,7 +36,7 @@ pub struct Message {
     /// Event type (e.g., "message", "ping", "notification")
     pub event_type: String,
 
-    /// JSON payload
+    /// JSON payload ([`NULL_DATA`] if the stored value is NULL)
     pub data: String,
 
     /// When the message was created
@@ -25,6 +45,27 @@ pub struct Message {
     /// When the message was delivered (None = undelivered)
     #[serde(default)]
     pub delivered_at: Option<DateTime<Utc>>,
+
+    /// Writer queue priority (not persisted)
+    #[serde(default)]
+    pub priority: Priority,
+
+    /// Coalescing key (not persisted)
+    ///
+    /// Within a pending batch, only the last message per
+    /// `(session_id, coalesce_key)` is written. Use for superseding events such
+    /// as `notifications/progress` for a given progress token.
+    #[serde(default)]
+    pub coalesce_key: Option<String>,
+
+    /// W3C trace context of the publishing request
+    ///
+    /// Persisted when [`Config::persist_traceparent`](crate::Config) is
+    /// enabled and recorded on the `deliver` span. See
+    /// [`trace_context`](crate::trace_context) for how it is filled in and
+    /// used with the `opentelemetry` feature.
+    #[serde(default)]
+    pub traceparent: Option<String>,
 }
 
 impl Message {
@@ -41,9 +82,30 @@ impl Message {
             data: data.into(),
             created_at: Utc::now(),
             delivered_at: None,
+            priority: Priority::Normal,
+            coalesce_key: None,
+            traceparent: None,
         }
     }
 
+    /// Builder pattern: set the writer queue priority
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Builder pattern: set the coalescing key
+    pub fn with_coalesce_key(mut self, key: impl Into<String>) -> Self {
+        self.coalesce_key = Some(key.into());
+        self
+    }
+
+    /// Builder pattern: attach a W3C `traceparent` header value
+    pub fn with_traceparent(mut self, traceparent: impl Into<String>) -> Self {
+        self.traceparent = Some(traceparent.into());
+        self
+    }
+
     /// Create a message with JSON data
     pub fn with_json<T: Serialize>(
         session_id: impl Into<String>,
@@ -174,6 +236,32 @@ mod tests {
         assert!(msg.delivered_at.is_some());
     }
 
+    #[test]
+    fn test_message_priority() {
+        let msg = Message::new("session-123", "message", "{}");
+        assert_eq!(msg.priority, Priority::Normal);
+
+        let msg = msg.with_priority(Priority::High);
+        assert_eq!(msg.priority, Priority::High);
+        assert!(Priority::High > Priority::Normal);
+    }
+
+    #[test]
+    fn test_message_coalesce_key() {
+        let msg = Message::new("session-123", "message", "{}");
+        assert!(msg.coalesce_key.is_none());
+
+        let msg = msg.with_coalesce_key("progress:tok-1");
+        assert_eq!(msg.coalesce_key.as_deref(), Some("progress:tok-1"));
+    }
+
+    #[test]
+    fn test_message_traceparent() {
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let msg = Message::new("session-123", "message", "{}").with_traceparent(traceparent);
+        assert_eq!(msg.traceparent.as_deref(), Some(traceparent));
+    }
+
     #[test]
     fn test_message_batch() {
         let mut batch = MessageBatch::with_capacity(10);
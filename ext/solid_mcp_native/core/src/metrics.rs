@@ -0,0 +1,223 @@
+//! Runtime metrics for solid-mcp-core
+//!
+//! Counters are lock-free atomics updated on the hot path; [`Stats`] is a
+//! point-in-time snapshot assembled by [`PubSub::stats`](crate::PubSub::stats).
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds for the batch size histogram (messages)
+pub const BATCH_SIZE_BUCKETS: &[u64] = &[1, 5, 10, 25, 50, 100, 250, 500, 1000];
+
+/// Upper bounds for the write latency histogram (microseconds)
+pub const WRITE_LATENCY_BUCKETS_US: &[u64] = &[
+    500, 1_000, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 500_000, 1_000_000, 5_000_000,
+];
+
+/// Fixed-bucket histogram with atomic counters
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [u64],
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    /// Create a histogram with the given (ascending) bucket upper bounds
+    pub fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: bounds.iter().map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Record an observation
+    pub fn observe(&self, value: u64) {
+        if let Some(i) = self.bounds.iter().position(|&le| value <= le) {
+            self.counts[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Take a snapshot with cumulative bucket counts
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(&self.counts)
+            .map(|(&le, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (le, cumulative)
+            })
+            .collect();
+
+        HistogramSnapshot {
+            buckets,
+            sum: self.sum.load(Ordering::Relaxed),
+            count: self.count.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters maintained by the [`MessageWriter`](crate::writer::MessageWriter)
+#[derive(Debug)]
+pub struct WriterMetrics {
+    pub(crate) enqueued: AtomicU64,
+    pub(crate) dropped: AtomicU64,
+    pub(crate) written: AtomicU64,
+    pub(crate) coalesced: AtomicU64,
+    pub(crate) failed_batches: AtomicU64,
+    pub(crate) batch_size: Histogram,
+    pub(crate) write_latency: Histogram,
+}
+
+impl Default for WriterMetrics {
+    fn default() -> Self {
+        Self {
+            enqueued: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            written: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+            failed_batches: AtomicU64::new(0),
+            batch_size: Histogram::new(BATCH_SIZE_BUCKETS),
+            write_latency: Histogram::new(WRITE_LATENCY_BUCKETS_US),
+        }
+    }
+}
+
+impl WriterMetrics {
+    /// Record the outcome of a batch write
+    pub(crate) fn record_batch(&self, size: usize, elapsed: Duration, ok: bool) {
+        self.batch_size.observe(size as u64);
+        self.write_latency.observe(elapsed.as_micros() as u64);
+        if ok {
+            self.written.fetch_add(size as u64, Ordering::Relaxed);
+        } else {
+            self.failed_batches.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Snapshot of a [`Histogram`]
+#[derive(Debug, Clone, Serialize)]
+pub struct HistogramSnapshot {
+    /// `(upper_bound, cumulative_count)` pairs; observations above the last
+    /// bound are only included in `count`
+    pub buckets: Vec<(u64, u64)>,
+    /// Sum of all observations
+    pub sum: u64,
+    /// Number of observations
+    pub count: u64,
+}
+
+/// Writer queue and throughput statistics
+#[derive(Debug, Clone, Serialize)]
+pub struct WriterStats {
+    /// Messages currently waiting in the normal queue
+    pub queue_depth: usize,
+    /// Messages currently waiting in the priority queue
+    pub priority_queue_depth: usize,
+    /// Capacity of each queue
+    pub queue_capacity: usize,
+    /// Messages accepted into a queue
+    pub enqueued: u64,
+    /// Messages rejected because a queue was full
+    pub dropped: u64,
+    /// Messages successfully written to the database
+    pub written: u64,
+    /// Messages dropped by coalescing
+    pub coalesced: u64,
+    /// Batch writes that failed
+    pub failed_batches: u64,
+    /// Times the worker task was restarted after a panic
+    pub restarts: u64,
+    /// Error that caused the most recent restart
+    pub last_error: Option<String>,
+    /// Messages per batch write
+    pub batch_size: HistogramSnapshot,
+    /// Batch write latency in microseconds
+    pub write_latency_us: HistogramSnapshot,
+}
+
+/// Per-session subscriber statistics
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriberStats {
+    /// Session ID
+    pub session_id: String,
+    /// Messages delivered to the callback
+    pub delivered: u64,
+    /// Last delivered message ID
+    pub cursor: i64,
+    /// Highest message ID in the table, across all sessions, minus `cursor`
+    ///
+    /// Counts other sessions' messages too, so it measures how far behind the
+    /// global head the subscriber is rather than its own backlog. `None` if
+    /// the database couldn't be queried.
+    pub behind_head: Option<i64>,
+    /// Times the subscriber task was restarted
+    pub restarts: u64,
+    /// Error that caused the most recent restart
+    pub last_error: Option<String>,
+}
+
+/// Connection pool statistics
+#[derive(Debug, Clone, Serialize)]
+pub struct DbStats {
+    /// Backend name (`sqlite` or `postgres`)
+    pub backend: &'static str,
+    /// Open connections
+    pub size: u32,
+    /// Idle connections
+    pub idle: usize,
+    /// Configured maximum connections
+    pub max_connections: u32,
+}
+
+/// Point-in-time snapshot of engine metrics
+#[derive(Debug, Clone, Serialize)]
+pub struct Stats {
+    /// Writer statistics
+    pub writer: WriterStats,
+    /// Subscriber statistics, one entry per active session
+    pub subscribers: Vec<SubscriberStats>,
+    /// Messages dropped because a session's receive buffer was full
+    pub inbox_dropped: u64,
+    /// Database pool statistics
+    pub db: DbStats,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_cumulative_buckets() {
+        let histogram = Histogram::new(&[1, 10, 100]);
+        for value in [1, 5, 10, 50, 1000] {
+            histogram.observe(value);
+        }
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets, vec![(1, 1), (10, 3), (100, 4)]);
+        assert_eq!(snapshot.count, 5);
+        assert_eq!(snapshot.sum, 1066);
+    }
+
+    #[test]
+    fn test_writer_metrics_record_batch() {
+        let metrics = WriterMetrics::default();
+        metrics.record_batch(10, Duration::from_millis(2), true);
+        metrics.record_batch(5, Duration::from_millis(1), false);
+
+        assert_eq!(metrics.written.load(Ordering::Relaxed), 10);
+        assert_eq!(metrics.failed_batches.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.batch_size.snapshot().count, 2);
+        assert_eq!(metrics.write_latency.snapshot().sum, 3000);
+    }
+}
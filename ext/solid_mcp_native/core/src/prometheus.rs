@@ -0,0 +1,483 @@
+//! Prometheus text exposition for solid-mcp-core metrics
+//!
+//! Enabled with the `metrics` feature. [`render`] formats a [`Stats`] snapshot
+//! in the Prometheus text format (version 0.0.4); [`serve`] runs a minimal
+//! HTTP listener that answers `GET /metrics`.
+
+use crate::metrics::{HistogramSnapshot, Stats};
+use crate::{Error, Result};
+use std::fmt::Write;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Content type for the Prometheus text format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Render a stats snapshot in Prometheus text format
+pub fn render(stats: &Stats) -> String {
+    let mut out = String::new();
+    let w = &stats.writer;
+
+    header(
+        &mut out,
+        "solid_mcp_writer_queue_depth",
+        "gauge",
+        "Messages waiting in the writer queue",
+    );
+    sample(
+        &mut out,
+        "solid_mcp_writer_queue_depth",
+        &[("lane", "normal")],
+        w.queue_depth,
+    );
+    sample(
+        &mut out,
+        "solid_mcp_writer_queue_depth",
+        &[("lane", "priority")],
+        w.priority_queue_depth,
+    );
+
+    gauge(
+        &mut out,
+        "solid_mcp_writer_queue_capacity",
+        "Capacity of each writer queue",
+        w.queue_capacity,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_messages_enqueued_total",
+        "Messages accepted into the writer queue",
+        w.enqueued,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_messages_dropped_total",
+        "Messages rejected because the writer queue was full",
+        w.dropped,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_messages_written_total",
+        "Messages written to the database",
+        w.written,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_messages_coalesced_total",
+        "Messages dropped by coalescing",
+        w.coalesced,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_failed_batches_total",
+        "Batch writes that failed",
+        w.failed_batches,
+    );
+    counter(
+        &mut out,
+        "solid_mcp_writer_restarts_total",
+        "Times the writer task was restarted",
+        w.restarts,
+    );
+
+    histogram(
+        &mut out,
+        "solid_mcp_writer_batch_size",
+        "Messages per batch write",
+        &w.batch_size,
+        1.0,
+    );
+    histogram(
+        &mut out,
+        "solid_mcp_writer_write_latency_seconds",
+        "Batch write latency",
+        &w.write_latency_us,
+        1e-6,
+    );
+
+    gauge(
+        &mut out,
+        "solid_mcp_subscribers",
+        "Active session subscribers",
+        stats.subscribers.len(),
+    );
+    // Aggregated, since per-session labels would create a series per
+    // connection
+    let subscribers = &stats.subscribers;
+    counter(
+        &mut out,
+        "solid_mcp_subscriber_delivered_total",
+        "Messages delivered across active subscribers",
+        subscribers.iter().map(|sub| sub.delivered).sum::<u64>(),
+    );
+    // Omitted when the database couldn't be queried
+    if let Some(behind) = subscribers.iter().filter_map(|sub| sub.behind_head).max() {
+        gauge(
+            &mut out,
+            "solid_mcp_subscriber_max_behind_head",
+            "Largest gap between a subscriber cursor and the highest message ID",
+            behind,
+        );
+    }
+    counter(
+        &mut out,
+        "solid_mcp_subscriber_restarts_total",
+        "Subscriber task restarts across active subscribers",
+        subscribers.iter().map(|sub| sub.restarts).sum::<u64>(),
+    );
+
+    counter(
+        &mut out,
+        "solid_mcp_inbox_messages_dropped_total",
+        "Messages dropped because a session's receive buffer was full",
+        stats.inbox_dropped,
+    );
+
+    let backend = [("backend", stats.db.backend)];
+    header(
+        &mut out,
+        "solid_mcp_db_pool_connections",
+        "gauge",
+        "Open database connections",
+    );
+    sample(
+        &mut out,
+        "solid_mcp_db_pool_connections",
+        &backend,
+        stats.db.size,
+    );
+    header(
+        &mut out,
+        "solid_mcp_db_pool_idle_connections",
+        "gauge",
+        "Idle database connections",
+    );
+    sample(
+        &mut out,
+        "solid_mcp_db_pool_idle_connections",
+        &backend,
+        stats.db.idle,
+    );
+    header(
+        &mut out,
+        "solid_mcp_db_pool_max_connections",
+        "gauge",
+        "Configured maximum database connections",
+    );
+    sample(
+        &mut out,
+        "solid_mcp_db_pool_max_connections",
+        &backend,
+        stats.db.max_connections,
+    );
+
+    out
+}
+
+/// Handle to a running metrics listener
+///
+/// The listener is aborted when the handle is dropped.
+pub struct MetricsServer {
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MetricsServer {
+    /// Stop the listener and wait for it to exit
+    pub async fn stop(mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        if let Some(handle) = &self.handle {
+            handle.abort();
+        }
+    }
+}
+
+/// Initial delay after a failed accept (e.g. EMFILE)
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Upper bound for the accept retry delay
+const MAX_ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Serve `GET /metrics` on `addr` until the returned server is stopped
+///
+/// `collect` is invoked for every scrape. Each connection is handled on its
+/// own task, so a slow client can't hold up other scrapes.
+pub async fn serve<F, Fut>(addr: SocketAddr, collect: F) -> Result<MetricsServer>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<Stats>> + Send + 'static,
+{
+    let listener = TcpListener::bind(addr).await.map_err(|e| {
+        Error::Config(format!(
+            "Failed to bind metrics listener on {}: {}",
+            addr, e
+        ))
+    })?;
+
+    info!("Prometheus metrics listening on http://{}/metrics", addr);
+
+    let collect = Arc::new(collect);
+    let handle = tokio::spawn(async move {
+        let mut backoff = ACCEPT_BACKOFF;
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!(
+                        "Metrics listener accept failed: {} (retry in {:?})",
+                        e, backoff
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_ACCEPT_BACKOFF);
+                    continue;
+                }
+            };
+            backoff = ACCEPT_BACKOFF;
+
+            debug!("Metrics scrape from {}", peer);
+            let collect = collect.clone();
+            tokio::spawn(async move {
+                let Some((stream, path)) = read_request_path(stream).await else {
+                    return;
+                };
+
+                let result = if path != "/metrics" {
+                    respond(stream, "404 Not Found", "text/plain", "").await
+                } else {
+                    match collect().await {
+                        Ok(stats) => respond(stream, "200 OK", CONTENT_TYPE, &render(&stats)).await,
+                        Err(e) => {
+                            warn!("Failed to collect metrics: {}", e);
+                            respond(stream, "500 Internal Server Error", "text/plain", "").await
+                        }
+                    }
+                };
+
+                if let Err(e) = result {
+                    debug!("Failed to write metrics response: {}", e);
+                }
+            });
+        }
+    });
+
+    Ok(MetricsServer {
+        handle: Some(handle),
+    })
+}
+
+/// Read the request head and return the request path
+async fn read_request_path(mut stream: TcpStream) -> Option<(TcpStream, String)> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut chunk = [0u8; 1024];
+
+    let read = async {
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < 8192 {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => return false,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        true
+    };
+
+    match tokio::time::timeout(Duration::from_secs(5), read).await {
+        Ok(true) => {}
+        _ => return None,
+    }
+
+    let head = String::from_utf8_lossy(&buf);
+    let mut parts = head.lines().next()?.split_whitespace();
+    let method = parts.next()?;
+    let path = parts.next()?.split('?').next()?;
+
+    // Anything other than GET is answered with 404
+    let path = if method == "GET" { path } else { "" };
+    Some((stream, path.to_string()))
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (key, val)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{}=\"{}\"", key, escape_label(val));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, "gauge", help);
+    sample(out, name, &[], value);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, value: impl std::fmt::Display) {
+    header(out, name, "counter", help);
+    sample(out, name, &[], value);
+}
+
+/// Write a histogram, multiplying bounds and sum by `scale` (e.g. µs to s)
+fn histogram(out: &mut String, name: &str, help: &str, snapshot: &HistogramSnapshot, scale: f64) {
+    header(out, name, "histogram", help);
+
+    let bucket = format!("{}_bucket", name);
+    for &(le, count) in &snapshot.buckets {
+        let le = (le as f64 * scale).to_string();
+        sample(out, &bucket, &[("le", &le)], count);
+    }
+    sample(out, &bucket, &[("le", "+Inf")], snapshot.count);
+    sample(
+        out,
+        &format!("{}_sum", name),
+        &[],
+        snapshot.sum as f64 * scale,
+    );
+    sample(out, &format!("{}_count", name), &[], snapshot.count);
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{DbStats, SubscriberStats, WriterStats};
+
+    fn sample_stats() -> Stats {
+        Stats {
+            writer: WriterStats {
+                queue_depth: 3,
+                priority_queue_depth: 0,
+                queue_capacity: 10_000,
+                enqueued: 10,
+                dropped: 1,
+                written: 6,
+                coalesced: 0,
+                failed_batches: 0,
+                restarts: 1,
+                last_error: Some("panicked: boom".to_string()),
+                batch_size: HistogramSnapshot {
+                    buckets: vec![(1, 0), (10, 1)],
+                    sum: 6,
+                    count: 1,
+                },
+                write_latency_us: HistogramSnapshot {
+                    buckets: vec![(500, 0), (1_000, 1)],
+                    sum: 750,
+                    count: 1,
+                },
+            },
+            subscribers: vec![SubscriberStats {
+                session_id: "a\"b".to_string(),
+                delivered: 4,
+                cursor: 4,
+                behind_head: Some(2),
+                restarts: 0,
+                last_error: None,
+            }],
+            inbox_dropped: 0,
+            db: DbStats {
+                backend: "sqlite",
+                size: 1,
+                idle: 1,
+                max_connections: 1,
+            },
+        }
+    }
+
+    #[test]
+    fn test_render_prometheus() {
+        let text = render(&sample_stats());
+
+        assert!(text.contains("# TYPE solid_mcp_writer_queue_depth gauge\n"));
+        assert!(text.contains("solid_mcp_writer_queue_depth{lane=\"normal\"} 3\n"));
+        assert!(text.contains("solid_mcp_writer_messages_dropped_total 1\n"));
+        assert!(text.contains("solid_mcp_writer_restarts_total 1\n"));
+        assert!(text.contains("solid_mcp_writer_batch_size_bucket{le=\"10\"} 1\n"));
+        assert!(text.contains("solid_mcp_writer_batch_size_bucket{le=\"+Inf\"} 1\n"));
+        assert!(text.contains("solid_mcp_writer_write_latency_seconds_bucket{le=\"0.001\"} 1\n"));
+        assert!(text.contains("solid_mcp_writer_write_latency_seconds_sum 0.00075\n"));
+        assert!(text.contains("solid_mcp_subscribers 1\n"));
+        assert!(text.contains("solid_mcp_subscriber_delivered_total 4\n"));
+        assert!(text.contains("solid_mcp_subscriber_max_behind_head 2\n"));
+        assert!(!text.contains("session_id"));
+        assert!(text.contains("solid_mcp_db_pool_max_connections{backend=\"sqlite\"} 1\n"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_metrics() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let server = serve(addr, || async { Ok(sample_stats()) }).await.unwrap();
+
+        // An idle client doesn't block other scrapes
+        let _idle = TcpStream::connect(addr).await.unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("solid_mcp_writer_messages_enqueued_total 10\n"));
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /other HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        server.stop().await;
+    }
+}
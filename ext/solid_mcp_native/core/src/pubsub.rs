@@ -5,12 +5,19 @@
 //! - Non-blocking message broadcasting
 //! - Graceful shutdown
 
-use crate::db::{Database, DbPool};
+use crate::db::{Database, DbPool, migrate, schema};
+use crate::health::{DbHealth, Health, PING_TIMEOUT, SubscriberHealth};
+use crate::inbox::Inbox;
+use crate::metrics::{Stats, SubscriberStats};
+#[cfg(feature = "metrics")]
+use crate::prometheus::MetricsServer;
 use crate::subscriber::{MessageCallback, Subscriber};
 use crate::writer::MessageWriter;
 use crate::{Config, Error, Message, Result};
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
@@ -19,37 +26,83 @@ pub struct PubSub {
     db: Arc<DbPool>,
     config: Config,
     writer: Arc<MessageWriter>,
-    subscribers: RwLock<HashMap<String, Subscriber>>,
+    subscribers: Arc<RwLock<HashMap<String, Subscriber>>>,
+    inboxes: RwLock<HashMap<String, Arc<Inbox>>>,
+    inbox_dropped: Arc<AtomicU64>,
+    #[cfg(feature = "metrics")]
+    metrics_server: tokio::sync::Mutex<Option<MetricsServer>>,
 }
 
 impl PubSub {
     /// Create a new pub/sub engine
     pub async fn new(config: Config) -> Result<Self> {
+        config.validate()?;
         let db = Arc::new(DbPool::new(&config).await?);
-        let writer = Arc::new(MessageWriter::new(db.clone(), &config).await?);
+        let pubsub = Self::with_db(db, config).await?;
 
         info!("PubSub engine initialized");
 
-        Ok(Self {
-            db,
-            config,
-            writer,
-            subscribers: RwLock::new(HashMap::new()),
-        })
+        Ok(pubsub)
     }
 
     /// Create a new pub/sub engine with an existing database pool
+    ///
+    /// Fails if the schema is missing, at an unexpected version, or doesn't
+    /// match what the backends expect; see [`migrate::verify`] and
+    /// [`schema::check`].
     pub async fn with_db(db: Arc<DbPool>, config: Config) -> Result<Self> {
+        config.validate()?;
+        migrate::verify(&db).await?;
+        schema::check(&db, config.persist_traceparent).await?;
         let writer = Arc::new(MessageWriter::new(db.clone(), &config).await?);
+        let subscribers = Arc::new(RwLock::new(HashMap::new()));
+        let inbox_dropped = Arc::new(AtomicU64::new(0));
+
+        #[cfg(feature = "metrics")]
+        let metrics_server = match config.metrics_addr {
+            Some(addr) => {
+                let (db, writer, subscribers, dropped) = (
+                    db.clone(),
+                    writer.clone(),
+                    subscribers.clone(),
+                    inbox_dropped.clone(),
+                );
+                let collect = move || {
+                    let (db, writer, subscribers, dropped) = (
+                        db.clone(),
+                        writer.clone(),
+                        subscribers.clone(),
+                        dropped.clone(),
+                    );
+                    async move { collect_stats(&db, &writer, &subscribers, &dropped).await }
+                };
+                Some(crate::prometheus::serve(addr, collect).await?)
+            }
+            None => None,
+        };
+
+        #[cfg(not(feature = "metrics"))]
+        if config.metrics_addr.is_some() {
+            tracing::warn!("metrics_addr is set but the `metrics` feature is disabled");
+        }
 
         Ok(Self {
             db,
             config,
             writer,
-            subscribers: RwLock::new(HashMap::new()),
+            subscribers,
+            inboxes: RwLock::new(HashMap::new()),
+            inbox_dropped,
+            #[cfg(feature = "metrics")]
+            metrics_server: tokio::sync::Mutex::new(metrics_server),
         })
     }
 
+    /// Configuration the engine was started with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Broadcast a message to a session (non-blocking)
     ///
     /// Returns `true` if the message was enqueued, `false` if the queue was full.
@@ -74,6 +127,25 @@ impl PubSub {
         self.writer.enqueue_async(message).await
     }
 
+    /// Publish a prepared message (non-blocking)
+    ///
+    /// Use this to set message options such as [`Priority`](crate::Priority).
+    /// Returns `true` if the message was enqueued, `false` if the queue was full.
+    pub fn publish(&self, message: Message) -> Result<bool> {
+        self.writer.enqueue(message)
+    }
+
+    /// Publish a prepared message, failing with
+    /// [`Error::QueueFull`](crate::Error::QueueFull) if the queue is full
+    pub fn try_publish(&self, message: Message) -> Result<()> {
+        self.writer.try_enqueue(message)
+    }
+
+    /// Publish a prepared message (async, waits if queue is full)
+    pub async fn publish_async(&self, message: Message) -> Result<()> {
+        self.writer.enqueue_async(message).await
+    }
+
     /// Subscribe to messages for a session
     ///
     /// The callback will be invoked for each new message.
@@ -88,10 +160,7 @@ impl PubSub {
         let mut subscribers = self.subscribers.write().await;
 
         if subscribers.contains_key(&session_id) {
-            return Err(Error::Config(format!(
-                "Already subscribed to session {}",
-                session_id
-            )));
+            return Err(Error::AlreadySubscribed(session_id));
         }
 
         let subscriber =
@@ -101,8 +170,55 @@ impl PubSub {
         Ok(())
     }
 
+    /// Receive messages for a session, waiting up to `timeout`
+    ///
+    /// The first call for a session subscribes it and buffers messages until
+    /// they are received, up to [`Config::max_queue_size`](crate::Config)
+    /// per session; beyond that the oldest are dropped and counted in
+    /// [`Stats::inbox_dropped`]. The wait is capped at
+    /// [`Config::max_wait_time`](crate::Config); an empty batch means the wait
+    /// timed out. Fails with [`Error::SessionNotFound`] if the session is
+    /// unsubscribed while waiting.
+    pub async fn receive(&self, session_id: &str, timeout: Duration) -> Result<Vec<Message>> {
+        let inbox = self.inbox(session_id).await?;
+        let timeout = timeout.min(self.config.max_wait_time);
+
+        match inbox.recv(timeout).await {
+            Some(messages) => Ok(messages),
+            None => {
+                let mut inboxes = self.inboxes.write().await;
+                if inboxes
+                    .get(session_id)
+                    .is_some_and(|current| Arc::ptr_eq(current, &inbox))
+                {
+                    inboxes.remove(session_id);
+                }
+                Err(Error::SessionNotFound(session_id.to_string()))
+            }
+        }
+    }
+
+    async fn inbox(&self, session_id: &str) -> Result<Arc<Inbox>> {
+        if let Some(inbox) = self.inboxes.read().await.get(session_id) {
+            return Ok(inbox.clone());
+        }
+
+        let mut inboxes = self.inboxes.write().await;
+        if let Some(inbox) = inboxes.get(session_id) {
+            return Ok(inbox.clone());
+        }
+
+        let (inbox, callback) = Inbox::new(self.config.max_queue_size, self.inbox_dropped.clone());
+        self.subscribe(session_id, callback).await?;
+        let inbox = Arc::new(inbox);
+        inboxes.insert(session_id.to_string(), inbox.clone());
+        Ok(inbox)
+    }
+
     /// Unsubscribe from a session
     pub async fn unsubscribe(&self, session_id: &str) -> Result<()> {
+        self.inboxes.write().await.remove(session_id);
+
         let mut subscribers = self.subscribers.write().await;
 
         if let Some(subscriber) = subscribers.remove(session_id) {
@@ -115,13 +231,89 @@ impl PubSub {
     /// Check if subscribed to a session
     pub async fn is_subscribed(&self, session_id: &str) -> bool {
         let subscribers = self.subscribers.read().await;
-        subscribers.contains_key(session_id)
+        subscribers
+            .get(session_id)
+            .is_some_and(|subscriber| !subscriber.exited_unexpectedly())
     }
 
     /// Get the number of active subscriptions
+    ///
+    /// Subscribers whose task exited unexpectedly are not counted.
     pub async fn subscription_count(&self) -> usize {
         let subscribers = self.subscribers.read().await;
-        subscribers.len()
+        subscribers
+            .values()
+            .filter(|subscriber| !subscriber.exited_unexpectedly())
+            .count()
+    }
+
+    /// Snapshot writer, subscriber and database pool metrics
+    ///
+    /// Still succeeds when the database is unreachable; subscribers then
+    /// report [`behind_head`](SubscriberStats::behind_head) as `None`.
+    pub async fn stats(&self) -> Result<Stats> {
+        collect_stats(
+            &self.db,
+            &self.writer,
+            &self.subscribers,
+            &self.inbox_dropped,
+        )
+        .await
+    }
+
+    /// Report database reachability, writer and subscriber task health
+    pub async fn health(&self) -> Health {
+        let started = Instant::now();
+        let db = match tokio::time::timeout(PING_TIMEOUT, self.db.ping()).await {
+            Ok(Ok(())) => DbHealth {
+                reachable: true,
+                latency: Some(started.elapsed()),
+                error: None,
+            },
+            Ok(Err(e)) => DbHealth {
+                reachable: false,
+                latency: None,
+                error: Some(e.to_string()),
+            },
+            Err(_) => DbHealth {
+                reachable: false,
+                latency: None,
+                error: Some(format!("ping timed out after {:?}", PING_TIMEOUT)),
+            },
+        };
+
+        let writer = self.writer.stats();
+        let queue_saturation = writer.queue_depth.max(writer.priority_queue_depth) as f64
+            / writer.queue_capacity.max(1) as f64;
+
+        let subscribers = self.subscribers.read().await;
+        let dead_subscribers = subscribers
+            .values()
+            .filter(|subscriber| subscriber.exited_unexpectedly())
+            .count();
+        let subscribers = subscribers
+            .values()
+            .map(|subscriber| SubscriberHealth {
+                session_id: subscriber.session_id().to_string(),
+                state: subscriber.state(),
+            })
+            .collect();
+
+        Health {
+            db,
+            writer_alive: self.writer.is_alive(),
+            writer_state: self.writer.state(),
+            writer_last_failure_at: self.writer.last_failure_at(),
+            queue_saturation,
+            subscribers,
+            dead_subscribers,
+        }
+    }
+
+    /// Render engine metrics in Prometheus text format
+    #[cfg(feature = "metrics")]
+    pub async fn render_prometheus(&self) -> Result<String> {
+        Ok(crate::prometheus::render(&self.stats().await?))
     }
 
     /// Flush all pending messages to the database
@@ -153,6 +345,17 @@ impl PubSub {
 
     /// Shutdown the pub/sub engine gracefully
     pub async fn shutdown(self) -> Result<()> {
+        self.close().await
+    }
+
+    /// Shut down through a shared reference
+    ///
+    /// Stops every subscriber (pending [`receive`](Self::receive) calls fail
+    /// with [`Error::SessionNotFound`]), then flushes and stops the writer.
+    /// Later publishes fail with [`Error::Shutdown`]. Unlike
+    /// [`shutdown`](Self::shutdown) this doesn't need the last reference, so
+    /// it works while other threads are still using the engine.
+    pub async fn close(&self) -> Result<()> {
         info!("PubSub engine shutting down...");
 
         // Stop all subscribers
@@ -164,28 +367,65 @@ impl PubSub {
             }
         }
         drop(subscribers);
+        self.inboxes.write().await.clear();
 
-        // Shutdown writer (flushes remaining messages)
-        // Need to unwrap the Arc - this only works if we're the last holder
-        match Arc::try_unwrap(self.writer) {
-            Ok(writer) => writer.shutdown().await?,
-            Err(_) => {
-                tracing::warn!("Could not unwrap writer Arc, forcing flush");
-                // Best effort - can't fully shutdown
-            }
+        // Stop the metrics listener
+        #[cfg(feature = "metrics")]
+        if let Some(server) = self.metrics_server.lock().await.take() {
+            server.stop().await;
         }
 
+        // Shutdown writer (flushes remaining messages)
+        self.writer.close().await?;
+
         info!("PubSub engine shutdown complete");
         Ok(())
     }
 }
 
+async fn collect_stats(
+    db: &DbPool,
+    writer: &MessageWriter,
+    subscribers: &RwLock<HashMap<String, Subscriber>>,
+    inbox_dropped: &AtomicU64,
+) -> Result<Stats> {
+    let max_id = match db.max_id().await {
+        Ok(max_id) => Some(max_id),
+        Err(e) => {
+            tracing::warn!("Failed to read the message head for stats: {}", e);
+            None
+        }
+    };
+
+    let subscribers = subscribers.read().await;
+    let subscribers = subscribers
+        .values()
+        .map(|subscriber| {
+            let cursor = subscriber.cursor();
+            SubscriberStats {
+                session_id: subscriber.session_id().to_string(),
+                delivered: subscriber.delivered(),
+                cursor,
+                behind_head: max_id.map(|max_id| (max_id - cursor).max(0)),
+                restarts: subscriber.restarts(),
+                last_error: subscriber.last_error(),
+            }
+        })
+        .collect();
+
+    Ok(Stats {
+        writer: writer.stats(),
+        subscribers,
+        inbox_dropped: inbox_dropped.load(Ordering::Relaxed),
+        db: db.pool_stats(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::sqlite::SqlitePool;
     use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::time::Duration;
 
     async fn create_test_pubsub(config: Config) -> PubSub {
         let sqlite = SqlitePool::new("sqlite::memory:").await.unwrap();
@@ -277,6 +517,94 @@ mod tests {
         pubsub.shutdown().await.unwrap();
     }
 
+    #[tokio::test]
+    async fn test_pubsub_receive() {
+        let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
+        let pubsub = create_test_pubsub(config).await;
+
+        let messages = pubsub
+            .receive("session-1", Duration::from_millis(20))
+            .await
+            .unwrap();
+        assert!(messages.is_empty());
+        assert!(pubsub.is_subscribed("session-1").await);
+
+        pubsub.broadcast("session-1", "message", "1").unwrap();
+        pubsub.broadcast("session-1", "message", "2").unwrap();
+        pubsub.flush().await.unwrap();
+
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            received.extend(
+                pubsub
+                    .receive("session-1", Duration::from_secs(1))
+                    .await
+                    .unwrap(),
+            );
+        }
+        assert_eq!(received[0].data, "1");
+        assert_eq!(received[1].data, "2");
+
+        pubsub.unsubscribe("session-1").await.unwrap();
+        assert!(!pubsub.is_subscribed("session-1").await);
+
+        pubsub.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_close_while_shared() {
+        let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
+        let pubsub = Arc::new(create_test_pubsub(config).await);
+
+        // A receive in progress holds another reference
+        let receiver = pubsub.clone();
+        let waiting =
+            tokio::spawn(
+                async move { receiver.receive("session-1", Duration::from_secs(30)).await },
+            );
+        while !pubsub.is_subscribed("session-1").await {
+            tokio::task::yield_now().await;
+        }
+
+        pubsub.broadcast("session-2", "message", "queued").unwrap();
+        pubsub.close().await.unwrap();
+
+        assert!(matches!(
+            waiting.await.unwrap(),
+            Err(Error::SessionNotFound(_))
+        ));
+        assert_eq!(
+            pubsub
+                .db
+                .fetch_after("session-2", 0, 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(matches!(
+            pubsub.broadcast("session-2", "message", "late"),
+            Err(Error::Shutdown)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_already_subscribed() {
+        let pubsub = create_test_pubsub(Config::new("sqlite::memory:")).await;
+
+        pubsub
+            .subscribe("session-1", Box::new(|_| {}))
+            .await
+            .unwrap();
+        let err = pubsub
+            .subscribe("session-1", Box::new(|_| {}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::AlreadySubscribed(ref s) if s == "session-1"));
+
+        pubsub.shutdown().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_pubsub_unsubscribe() {
         let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
@@ -306,4 +634,84 @@ mod tests {
 
         pubsub.shutdown().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_pubsub_stats() {
+        let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
+
+        let pubsub = create_test_pubsub(config).await;
+
+        pubsub
+            .subscribe("session-1", Box::new(|_| {}))
+            .await
+            .unwrap();
+
+        pubsub.broadcast("session-1", "msg", "{}").unwrap();
+        pubsub.broadcast("session-2", "msg", "{}").unwrap();
+        pubsub.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let stats = pubsub.stats().await.unwrap();
+        assert_eq!(stats.writer.enqueued, 2);
+        assert_eq!(stats.writer.written, 2);
+        assert_eq!(stats.db.backend, "sqlite");
+        assert_eq!(stats.subscribers.len(), 1);
+
+        let subscriber = &stats.subscribers[0];
+        assert_eq!(subscriber.session_id, "session-1");
+        assert_eq!(subscriber.delivered, 1);
+        assert_eq!(subscriber.cursor, 1);
+        // session-2's message counts towards the global head
+        assert_eq!(subscriber.behind_head, Some(1));
+
+        // Counters survive an unreachable database
+        let DbPool::Sqlite(pool) = &*pubsub.db else {
+            unreachable!()
+        };
+        pool.writer_pool().close().await;
+        let stats = pubsub.stats().await.unwrap();
+        assert_eq!(stats.writer.written, 2);
+        assert_eq!(stats.subscribers[0].behind_head, None);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn test_pubsub_render_prometheus() {
+        let pubsub = create_test_pubsub(Config::new("sqlite::memory:")).await;
+
+        pubsub.broadcast("session-1", "msg", "{}").unwrap();
+        pubsub.flush().await.unwrap();
+
+        let text = pubsub.render_prometheus().await.unwrap();
+        assert!(text.contains("solid_mcp_writer_messages_written_total 1\n"));
+        assert!(text.contains("solid_mcp_db_pool_max_connections{backend=\"sqlite\"} 1\n"));
+
+        pubsub.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pubsub_health() {
+        let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
+        let pubsub = create_test_pubsub(config).await;
+
+        pubsub
+            .subscribe("session-1", Box::new(|_| {}))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let health = pubsub.health().await;
+        assert!(health.is_healthy());
+        assert!(health.db.reachable);
+        assert!(health.writer_alive);
+        assert_eq!(health.queue_saturation, 0.0);
+        assert_eq!(health.dead_subscribers, 0);
+        assert_eq!(health.subscribers.len(), 1);
+        assert_eq!(
+            health.subscribers[0].state,
+            crate::subscriber::SubscriberState::Polling
+        );
+
+        pubsub.shutdown().await.unwrap();
+    }
 }
@@ -3,25 +3,88 @@
 //! Supports two modes:
 //! - PostgreSQL: Uses LISTEN/NOTIFY for real-time delivery (no polling)
 //! - SQLite: Falls back to efficient async polling
+//!
+//! Each loop runs under a supervisor that restarts it from the last delivered
+//! cursor if it fails (e.g. the LISTEN connection drops) or the callback panics.
 
 #[cfg(feature = "postgres")]
 use crate::db::postgres::PostgresPool;
 use crate::db::{Database, DbPool};
-use crate::{Config, Message, Result};
+use crate::supervisor::{TaskStatus, supervise};
+use crate::{Config, Message, Result, trace_context};
+use serde::Serialize;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU8, AtomicU64, Ordering};
 use std::time::Duration;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info, warn};
+use tracing::{Span, debug, error, field, info, info_span, instrument, warn};
 
 /// Callback type for message delivery
 pub type MessageCallback = Box<dyn Fn(Message) + Send + Sync + 'static>;
 
+/// Connection state of a subscriber task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriberState {
+    /// Task spawned, not yet receiving
+    Starting,
+    /// Polling the database (SQLite)
+    Polling,
+    /// LISTEN connection established (PostgreSQL)
+    Listening,
+    /// LISTEN connection failed or was lost
+    Disconnected,
+    /// Task exited
+    Stopped,
+}
+
+impl SubscriberState {
+    /// State name as reported in health checks
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Starting => "starting",
+            Self::Polling => "polling",
+            Self::Listening => "listening",
+            Self::Disconnected => "disconnected",
+            Self::Stopped => "stopped",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Starting,
+            1 => Self::Polling,
+            2 => Self::Listening,
+            3 => Self::Disconnected,
+            _ => Self::Stopped,
+        }
+    }
+}
+
+/// State shared between a [`Subscriber`] handle and its task
+struct Shared {
+    shutdown: AtomicBool,
+    last_id: AtomicI64,
+    delivered: AtomicU64,
+    state: AtomicU8,
+    status: TaskStatus,
+}
+
+impl Shared {
+    fn set_state(&self, state: SubscriberState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+}
+
 /// A subscriber for a specific session
 pub struct Subscriber {
     session_id: String,
     handle: JoinHandle<()>,
-    shutdown: Arc<AtomicBool>,
+    shared: Arc<Shared>,
 }
 
 impl Subscriber {
@@ -35,70 +98,60 @@ impl Subscriber {
         callback: MessageCallback,
     ) -> Result<Self> {
         let session_id = session_id.into();
-        let shutdown = Arc::new(AtomicBool::new(false));
-        let shutdown_clone = shutdown.clone();
         let session_clone = session_id.clone();
         let polling_interval = config.polling_interval;
 
         // Get initial last_id
-        let last_id = Arc::new(AtomicI64::new(db.max_id().await?));
-
-        let handle = match &*db {
-            #[cfg(feature = "postgres")]
-            DbPool::Postgres(pg) => {
-                // Use LISTEN/NOTIFY for PostgreSQL
-                let pg_clone = pg.clone();
-                let db_clone = db.clone();
-                tokio::spawn(async move {
-                    postgres_subscriber_loop(
-                        session_clone,
-                        pg_clone,
-                        db_clone,
-                        last_id,
-                        shutdown_clone,
-                        callback,
-                    )
-                    .await
-                })
-            }
-            #[cfg(feature = "sqlite")]
-            DbPool::Sqlite(_) => {
-                // Use polling for SQLite
-                tokio::spawn(async move {
-                    polling_subscriber_loop(
-                        session_clone,
-                        db,
-                        last_id,
-                        polling_interval,
-                        shutdown_clone,
-                        callback,
-                    )
-                    .await
-                })
-            }
-        };
+        let shared = Arc::new(Shared {
+            shutdown: AtomicBool::new(false),
+            last_id: AtomicI64::new(db.max_id().await?),
+            delivered: AtomicU64::new(0),
+            state: AtomicU8::new(SubscriberState::Starting as u8),
+            status: TaskStatus::default(),
+        });
+        let loop_shared = shared.clone();
+        let callback = Arc::new(callback);
+
+        let handle = tokio::spawn(async move {
+            let name = format!("Subscriber {}", session_clone);
+            let spawn = || {
+                let session_id = session_clone.clone();
+                let db = db.clone();
+                let shared = loop_shared.clone();
+                let callback = callback.clone();
+                async move {
+                    subscriber_loop(session_id, db, &shared, polling_interval, &callback).await
+                }
+            };
+            supervise(
+                &name,
+                &loop_shared.status,
+                || loop_shared.is_shutdown(),
+                spawn,
+            )
+            .await;
+        });
 
         info!("Subscriber started for session {}", session_id);
 
         Ok(Self {
             session_id,
             handle,
-            shutdown,
+            shared,
         })
     }
 
     /// Stop the subscriber
-    pub async fn stop(self) -> Result<()> {
+    pub async fn stop(mut self) -> Result<()> {
         info!("Stopping subscriber for session {}", self.session_id);
-        self.shutdown.store(true, Ordering::SeqCst);
+        self.shared.shutdown.store(true, Ordering::SeqCst);
 
         // Wait for the task to complete (with timeout)
-        tokio::select! {
-            _ = self.handle => {
-                debug!("Subscriber task completed");
-            }
-            _ = tokio::time::sleep(Duration::from_secs(5)) => {
+        match tokio::time::timeout(Duration::from_secs(5), &mut self.handle).await {
+            Ok(_) => debug!("Subscriber task completed"),
+            Err(_) => {
                 warn!("Subscriber task did not complete in time, aborting");
+                self.handle.abort();
             }
         }
 
@@ -109,35 +162,113 @@ impl Subscriber {
     pub fn session_id(&self) -> &str {
         &self.session_id
     }
+
+    /// Get the ID of the last delivered message
+    pub fn cursor(&self) -> i64 {
+        self.shared.last_id.load(Ordering::SeqCst)
+    }
+
+    /// Get the number of messages delivered to the callback
+    pub fn delivered(&self) -> u64 {
+        self.shared.delivered.load(Ordering::Relaxed)
+    }
+
+    /// Get the connection state of the subscriber task
+    pub fn state(&self) -> SubscriberState {
+        SubscriberState::from_u8(self.shared.state.load(Ordering::SeqCst))
+    }
+
+    /// Check if the task exited without being stopped
+    pub fn exited_unexpectedly(&self) -> bool {
+        self.handle.is_finished() && !self.shared.is_shutdown()
+    }
+
+    /// Get the number of times the subscriber loop was restarted
+    pub fn restarts(&self) -> u64 {
+        self.shared.status.restarts()
+    }
+
+    /// Get the error that caused the most recent restart
+    pub fn last_error(&self) -> Option<String> {
+        self.shared.status.last_error()
+    }
+}
+
+/// Fetch undelivered messages for a session inside a `fetch_after` span
+#[instrument(name = "fetch_after", skip(db), fields(count = field::Empty))]
+async fn fetch(db: &DbPool, session_id: &str, after_id: i64, limit: i64) -> Result<Vec<Message>> {
+    let messages = db.fetch_after(session_id, after_id, limit).await?;
+    Span::current().record("count", messages.len());
+    Ok(messages)
+}
+
+/// Hand a message to the callback inside a `deliver` span and advance the cursor
+///
+/// The span records the message's `traceparent`; with the `opentelemetry`
+/// feature it is also parented to the publishing span.
+fn deliver(msg: Message, callback: &MessageCallback, shared: &Shared) {
+    let span = info_span!(
+        "deliver",
+        session_id = %msg.session_id,
+        message_id = msg.id,
+        event_type = %msg.event_type,
+        traceparent = msg.traceparent.as_deref(),
+    );
+    if let Some(traceparent) = &msg.traceparent {
+        trace_context::set_parent(&span, traceparent);
+    }
+    let _enter = span.enter();
+
+    let msg_id = msg.id;
+    callback(msg);
+    shared.delivered.fetch_add(1, Ordering::Relaxed);
+    shared.last_id.store(msg_id, Ordering::SeqCst);
+}
+
+/// Run the delivery loop for the pool's backend
+async fn subscriber_loop(
+    session_id: String,
+    db: Arc<DbPool>,
+    shared: &Shared,
+    polling_interval: Duration,
+    callback: &MessageCallback,
+) -> Result<()> {
+    match &*db {
+        #[cfg(feature = "postgres")]
+        DbPool::Postgres(pg) => {
+            // Use LISTEN/NOTIFY for PostgreSQL
+            postgres_subscriber_loop(session_id, pg.clone(), db.clone(), shared, callback).await
+        }
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(_) => {
+            // Use polling for SQLite
+            polling_subscriber_loop(session_id, db.clone(), shared, polling_interval, callback)
+                .await
+        }
+    }
 }
 
 /// Polling-based subscriber loop (for SQLite)
 async fn polling_subscriber_loop(
     session_id: String,
     db: Arc<DbPool>,
-    last_id: Arc<AtomicI64>,
+    shared: &Shared,
     polling_interval: Duration,
-    shutdown: Arc<AtomicBool>,
-    callback: MessageCallback,
-) {
+    callback: &MessageCallback,
+) -> Result<()> {
     debug!(
         "Starting polling subscriber for session {} (interval: {:?})",
         session_id, polling_interval
     );
+    shared.set_state(SubscriberState::Polling);
 
-    while !shutdown.load(Ordering::SeqCst) {
+    while !shared.is_shutdown() {
         // Fetch new messages
-        let current_last_id = last_id.load(Ordering::SeqCst);
-        match db.fetch_after(&session_id, current_last_id, 100).await {
+        let current_last_id = shared.last_id.load(Ordering::SeqCst);
+        match fetch(&db, &session_id, current_last_id, 100).await {
             Ok(messages) => {
                 for msg in messages {
-                    let msg_id = msg.id;
-
-                    // Deliver to callback
-                    callback(msg);
-
-                    // Update last_id
-                    last_id.store(msg_id, Ordering::SeqCst);
+                    deliver(msg, callback, shared);
                 }
             }
             Err(e) => {
@@ -149,7 +280,7 @@ async fn polling_subscriber_loop(
         tokio::select! {
             _ = tokio::time::sleep(polling_interval) => {}
             _ = async {
-                while !shutdown.load(Ordering::SeqCst) {
+                while !shared.is_shutdown() {
                     tokio::time::sleep(Duration::from_millis(10)).await;
                 }
             } => {
@@ -159,6 +290,8 @@ async fn polling_subscriber_loop(
     }
 
     debug!("Polling subscriber for session {} stopped", session_id);
+    shared.set_state(SubscriberState::Stopped);
+    Ok(())
 }
 
 /// LISTEN/NOTIFY-based subscriber loop (for PostgreSQL)
@@ -167,23 +300,20 @@ async fn postgres_subscriber_loop(
     session_id: String,
     pg: PostgresPool,
     db: Arc<DbPool>,
-    last_id: Arc<AtomicI64>,
-    shutdown: Arc<AtomicBool>,
-    callback: MessageCallback,
-) {
+    shared: &Shared,
+    callback: &MessageCallback,
+) -> Result<()> {
     debug!(
         "Starting LISTEN/NOTIFY subscriber for session {}",
         session_id
     );
 
     // First, catch up on any missed messages
-    let current_last_id = last_id.load(Ordering::SeqCst);
-    match db.fetch_after(&session_id, current_last_id, 1000).await {
+    let current_last_id = shared.last_id.load(Ordering::SeqCst);
+    match fetch(&db, &session_id, current_last_id, 1000).await {
         Ok(messages) => {
             for msg in messages {
-                let msg_id = msg.id;
-                callback(msg);
-                last_id.store(msg_id, Ordering::SeqCst);
+                deliver(msg, callback, shared);
             }
         }
         Err(e) => {
@@ -202,12 +332,14 @@ async fn postgres_subscriber_loop(
                 "Failed to create listener for session {}: {}",
                 session_id, e
             );
-            return;
+            shared.set_state(SubscriberState::Disconnected);
+            return Err(e);
         }
     };
+    shared.set_state(SubscriberState::Listening);
 
     // Listen for notifications
-    while !shutdown.load(Ordering::SeqCst) {
+    while !shared.is_shutdown() {
         tokio::select! {
             notification = listener.recv() => {
                 match notification {
@@ -215,14 +347,12 @@ async fn postgres_subscriber_loop(
                         // Notification payload is the message ID
                         if let Ok(msg_id) = notif.payload().parse::<i64>() {
                             // Fetch the specific message
-                            let current = last_id.load(Ordering::SeqCst);
+                            let current = shared.last_id.load(Ordering::SeqCst);
                             if msg_id > current {
-                                match db.fetch_after(&session_id, current, 100).await {
+                                match fetch(&db, &session_id, current, 100).await {
                                     Ok(messages) => {
                                         for msg in messages {
-                                            let id = msg.id;
-                                            callback(msg);
-                                            last_id.store(id, Ordering::SeqCst);
+                                            deliver(msg, callback, shared);
                                         }
                                     }
                                     Err(e) => {
@@ -234,14 +364,15 @@ async fn postgres_subscriber_loop(
                     }
                     Err(e) => {
                         error!("Listener error for session {}: {}", session_id, e);
-                        // Reconnect logic could go here
-                        break;
+                        shared.set_state(SubscriberState::Disconnected);
+                        // The supervisor reconnects from the current cursor
+                        return Err(e.into());
                     }
                 }
             }
             _ = tokio::time::sleep(Duration::from_secs(1)) => {
                 // Periodic check for shutdown
-                if shutdown.load(Ordering::SeqCst) {
+                if shared.is_shutdown() {
                     break;
                 }
             }
@@ -252,6 +383,8 @@ async fn postgres_subscriber_loop(
         "LISTEN/NOTIFY subscriber for session {} stopped",
         session_id
     );
+    shared.set_state(SubscriberState::Stopped);
+    Ok(())
 }
 
 #[cfg(test)]
@@ -292,6 +425,45 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(100)).await;
 
         assert_eq!(received.load(Ordering::SeqCst), 5);
+        assert_eq!(subscriber.delivered(), 5);
+        assert_eq!(subscriber.cursor(), db.max_id().await.unwrap());
+
+        subscriber.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_restarts_after_callback_panic() {
+        let db = create_test_db().await;
+        let config = Config::new("sqlite::memory:").polling_interval(Duration::from_millis(10));
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.clone();
+
+        let callback: MessageCallback = Box::new(move |_msg| {
+            if attempts_clone.fetch_add(1, Ordering::SeqCst) == 0 {
+                panic!("callback failed");
+            }
+        });
+
+        let subscriber = Subscriber::new("session-1", db.clone(), &config, callback)
+            .await
+            .unwrap();
+
+        db.insert_batch(&[Message::new("session-1", "message", "{}")])
+            .await
+            .unwrap();
+
+        // Restart backoff is 100ms; the message is redelivered from the cursor
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(subscriber.delivered(), 1);
+        assert_eq!(subscriber.restarts(), 1);
+        assert_eq!(
+            subscriber.last_error().as_deref(),
+            Some("panicked: callback failed")
+        );
+        assert!(!subscriber.exited_unexpectedly());
 
         subscriber.stop().await.unwrap();
     }
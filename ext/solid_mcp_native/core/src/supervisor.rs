@@ -0,0 +1,241 @@
+//! Supervision for background tasks
+//!
+//! The writer and subscriber loops run inside a supervising task that
+//! respawns them when they panic or fail, with exponential backoff. The
+//! current [`TaskState`], restart count and last failure are kept in a
+//! [`TaskStatus`].
+
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::future::Future;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU8, AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::task::JoinError;
+use tracing::{error, warn};
+
+/// Initial delay before restarting a failed task
+pub const RESTART_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Upper bound for the restart delay
+pub const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Lifecycle state of a supervised task
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    /// The task is running
+    Running,
+    /// The task failed and is waiting to be restarted
+    BackingOff,
+    /// The supervisor exited (shutdown or completion)
+    Stopped,
+}
+
+impl TaskState {
+    /// State name as reported in health checks
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::BackingOff => "backing_off",
+            Self::Stopped => "stopped",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Running,
+            1 => Self::BackingOff,
+            _ => Self::Stopped,
+        }
+    }
+}
+
+/// Restart bookkeeping for a supervised task
+#[derive(Debug, Default)]
+pub struct TaskStatus {
+    state: AtomicU8,
+    restarts: AtomicU64,
+    last_error: Mutex<Option<String>>,
+    last_failure_at: Mutex<Option<DateTime<Utc>>>,
+}
+
+impl TaskStatus {
+    /// Current state of the task
+    pub fn state(&self) -> TaskState {
+        TaskState::from_u8(self.state.load(Ordering::SeqCst))
+    }
+
+    /// When the task last failed
+    pub fn last_failure_at(&self) -> Option<DateTime<Utc>> {
+        *self
+            .last_failure_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Number of times the task has been restarted
+    pub fn restarts(&self) -> u64 {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// Error that caused the most recent restart
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn set_state(&self, state: TaskState) {
+        self.state.store(state as u8, Ordering::SeqCst);
+    }
+
+    pub(crate) fn record_failure(&self, error: String) -> u64 {
+        *self.last_error.lock().unwrap_or_else(|e| e.into_inner()) = Some(error);
+        *self
+            .last_failure_at
+            .lock()
+            .unwrap_or_else(|e| e.into_inner()) = Some(Utc::now());
+        self.set_state(TaskState::BackingOff);
+        self.restarts.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// Run `spawn` until its task completes successfully or `should_stop` is set
+///
+/// A task that returns an error or panics is restarted after a backoff. The
+/// task is responsible for resuming from its own persisted state (cursor,
+/// queue receivers). `status` tracks the task's [`TaskState`].
+pub(crate) async fn supervise<S, F, Fut>(name: &str, status: &TaskStatus, should_stop: S, spawn: F)
+where
+    S: Fn() -> bool,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    run(name, status, should_stop, spawn).await;
+    status.set_state(TaskState::Stopped);
+}
+
+async fn run<S, F, Fut>(name: &str, status: &TaskStatus, should_stop: S, spawn: F)
+where
+    S: Fn() -> bool,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<()>> + Send + 'static,
+{
+    loop {
+        status.set_state(TaskState::Running);
+        let failure = match tokio::spawn(spawn()).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => e.to_string(),
+            Err(e) if e.is_cancelled() => return,
+            Err(e) => panic_message(e),
+        };
+
+        if should_stop() {
+            return;
+        }
+
+        let restarts = status.record_failure(failure.clone());
+        let delay = backoff(restarts);
+        error!(
+            "{} task failed: {} (restart #{} in {:?})",
+            name, failure, restarts, delay
+        );
+
+        tokio::time::sleep(delay).await;
+        if should_stop() {
+            return;
+        }
+        warn!("Restarting {} task", name);
+    }
+}
+
+/// Exponential backoff starting at [`RESTART_BACKOFF`], capped at [`MAX_RESTART_BACKOFF`]
+fn backoff(restarts: u64) -> Duration {
+    let exponent = restarts.saturating_sub(1).min(16) as u32;
+    RESTART_BACKOFF
+        .saturating_mul(2u32.pow(exponent))
+        .min(MAX_RESTART_BACKOFF)
+}
+
+fn panic_message(err: JoinError) -> String {
+    let panic = err.into_panic();
+    if let Some(msg) = panic.downcast_ref::<&str>() {
+        format!("panicked: {}", msg)
+    } else if let Some(msg) = panic.downcast_ref::<String>() {
+        format!("panicked: {}", msg)
+    } else {
+        "panicked".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), RESTART_BACKOFF);
+        assert_eq!(backoff(2), RESTART_BACKOFF * 2);
+        assert_eq!(backoff(100), MAX_RESTART_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_supervise_restarts_after_panic_and_error() {
+        let status = TaskStatus::default();
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let spawn = || {
+            let runs = runs.clone();
+            async move {
+                match runs.fetch_add(1, Ordering::SeqCst) {
+                    0 => panic!("boom"),
+                    1 => Err(Error::Config("listener lost".to_string())),
+                    _ => Ok(()),
+                }
+            }
+        };
+        supervise("test", &status, || false, spawn).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(status.restarts(), 2);
+        assert_eq!(
+            status.last_error().as_deref(),
+            Some("configuration error: listener lost")
+        );
+        assert!(status.last_failure_at().is_some());
+        assert_eq!(status.state(), TaskState::Stopped);
+    }
+
+    #[tokio::test]
+    async fn test_state_while_backing_off() {
+        let status = Arc::new(TaskStatus::default());
+
+        let supervisor = {
+            let status = status.clone();
+            tokio::spawn(async move {
+                supervise("test", &status, || false, || async { panic!("boom") }).await;
+            })
+        };
+        while status.restarts() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(status.state(), TaskState::BackingOff);
+
+        supervisor.abort();
+    }
+
+    #[tokio::test]
+    async fn test_supervise_stops_when_requested() {
+        let status = TaskStatus::default();
+
+        supervise("test", &status, || true, || async { Err(Error::Shutdown) }).await;
+
+        assert_eq!(status.restarts(), 0);
+    }
+}
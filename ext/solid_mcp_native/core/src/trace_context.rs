@@ -0,0 +1,157 @@
+//! W3C trace context propagation
+//!
+//! Messages carry the publisher's `traceparent`. With the `opentelemetry`
+//! feature, [`publish`](crate::PubSub::publish) fills it in from the current
+//! span when the caller didn't, and the subscriber's `deliver` span is
+//! parented to it, so one trace covers the request that published a message
+//! and the connection that delivered it. Without the feature the value is
+//! only recorded as a span field.
+
+/// Parsed `traceparent` header (version 00)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 16-byte trace ID
+    pub trace_id: u128,
+    /// 8-byte parent span ID
+    pub span_id: u64,
+    /// Trace flags (bit 0: sampled)
+    pub flags: u8,
+}
+
+impl TraceParent {
+    /// Parse a `traceparent` value, rejecting malformed or all-zero IDs
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.trim().split('-');
+        let version = parts.next()?;
+        let trace_id = parts.next()?;
+        let span_id = parts.next()?;
+        let flags = parts.next()?;
+        // Later versions may append fields; version 00 has exactly four
+        if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+            return None;
+        }
+        if trace_id.len() != 32 || span_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+
+        let parsed = Self {
+            trace_id: u128::from_str_radix(trace_id, 16).ok()?,
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        };
+        (parsed.trace_id != 0 && parsed.span_id != 0).then_some(parsed)
+    }
+
+    /// Format as a version 00 `traceparent` value
+    pub fn to_header(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id, self.span_id, self.flags
+        )
+    }
+}
+
+/// `traceparent` of the current span, if an OpenTelemetry layer is recording it
+#[cfg(feature = "opentelemetry")]
+pub(crate) fn current() -> Option<String> {
+    use opentelemetry::trace::TraceContextExt;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let context = tracing::Span::current().context();
+    let span = context.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| {
+        TraceParent {
+            trace_id: u128::from_be_bytes(span_context.trace_id().to_bytes()),
+            span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+            flags: span_context.trace_flags().to_u8(),
+        }
+        .to_header()
+    })
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+pub(crate) fn current() -> Option<String> {
+    None
+}
+
+/// Parent `span` to the remote span described by `traceparent`
+#[cfg(feature = "opentelemetry")]
+pub(crate) fn set_parent(span: &tracing::Span, traceparent: &str) {
+    use opentelemetry::Context;
+    use opentelemetry::trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState,
+    };
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let Some(parent) = TraceParent::parse(traceparent) else {
+        tracing::debug!("Ignoring invalid traceparent {:?}", traceparent);
+        return;
+    };
+    let span_context = SpanContext::new(
+        TraceId::from_bytes(parent.trace_id.to_be_bytes()),
+        SpanId::from_bytes(parent.span_id.to_be_bytes()),
+        TraceFlags::new(parent.flags),
+        true,
+        TraceState::default(),
+    );
+    let _ = span.set_parent(Context::new().with_remote_span_context(span_context));
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+pub(crate) fn set_parent(_span: &tracing::Span, _traceparent: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn test_parse_round_trip() {
+        let parent = TraceParent::parse(HEADER).unwrap();
+        assert_eq!(parent.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(parent.span_id, 0x00f067aa0ba902b7);
+        assert_eq!(parent.flags, 1);
+        assert_eq!(parent.to_header(), HEADER);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        for value in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-xyz92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(TraceParent::parse(value), None, "{:?}", value);
+        }
+        assert!(TraceParent::parse(&format!("01{}-extra", &HEADER[2..])).is_some());
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn test_deliver_span_continues_publisher_trace() {
+        use opentelemetry::trace::noop::NoopTracer;
+        use tracing_subscriber::layer::SubscriberExt;
+
+        assert_eq!(current(), None);
+
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(NoopTracer::new()));
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("deliver");
+            set_parent(&span, HEADER);
+            let _enter = span.enter();
+
+            let current = TraceParent::parse(&current().unwrap()).unwrap();
+            assert_eq!(
+                current.trace_id,
+                TraceParent::parse(HEADER).unwrap().trace_id
+            );
+        });
+    }
+}
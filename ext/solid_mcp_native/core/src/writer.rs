@@ -1,18 +1,34 @@
 //! Async message writer with batching
 //!
 //! Uses Tokio channels for non-blocking enqueue and background batch writes.
+//! High-priority messages travel on a separate lane and are always drained
+//! into the next batch before normal traffic. Messages sharing a coalescing
+//! key are collapsed to the latest one before each batch write. The worker
+//! is supervised and restarted on panic without losing queued messages; the
+//! batch being written when it panicked is dropped like a failed write, and
+//! pending [`flush`](MessageWriter::flush) calls complete after the restart.
 
 use crate::db::{Database, DbPool};
-use crate::{Config, Error, Message, Result};
-use std::sync::Arc;
+use crate::metrics::{WriterMetrics, WriterStats};
+use crate::supervisor::{TaskState, TaskStatus, supervise};
+use crate::{Config, Error, Message, Priority, Result, trace_context};
+use chrono::{DateTime, Utc};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info, warn};
+use tracing::{Span, debug, error, field, info, instrument, warn};
 
 /// Message writer that batches writes to the database
 pub struct MessageWriter {
     tx: mpsc::Sender<WriterCommand>,
-    handle: JoinHandle<()>,
+    priority_tx: mpsc::Sender<Message>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+    metrics: Arc<WriterMetrics>,
+    status: Arc<TaskStatus>,
 }
 
 enum WriterCommand {
@@ -25,11 +41,31 @@ impl MessageWriter {
     /// Create a new message writer
     pub async fn new(db: Arc<DbPool>, config: &Config) -> Result<Self> {
         let (tx, rx) = mpsc::channel(config.max_queue_size);
+        let (priority_tx, priority_rx) = mpsc::channel(config.max_queue_size);
         let batch_size = config.batch_size;
         let _shutdown_timeout = config.shutdown_timeout; // TODO: Use for timeout handling
+        let metrics = Arc::new(WriterMetrics::default());
+        let status = Arc::new(TaskStatus::default());
+
+        // Receivers and flush waiters outlive individual worker runs so a
+        // restart keeps the queue
+        let receivers = Arc::new(tokio::sync::Mutex::new((rx, priority_rx, Vec::new())));
+        let loop_metrics = metrics.clone();
+        let loop_status = status.clone();
 
         let handle = tokio::spawn(async move {
-            writer_loop(rx, db, batch_size).await;
+            let spawn = || {
+                let receivers = receivers.clone();
+                let db = db.clone();
+                let metrics = loop_metrics.clone();
+                async move {
+                    let mut receivers = receivers.lock().await;
+                    let (rx, priority_rx, flush_waiters) = &mut *receivers;
+                    writer_loop(rx, priority_rx, flush_waiters, db, batch_size, metrics).await;
+                    Ok(())
+                }
+            };
+            supervise("MessageWriter", &loop_status, || false, spawn).await;
             debug!("MessageWriter worker shutdown complete");
         });
 
@@ -38,29 +74,150 @@ impl MessageWriter {
             batch_size, config.max_queue_size
         );
 
-        Ok(Self { tx, handle })
+        Ok(Self {
+            tx,
+            priority_tx,
+            handle: Mutex::new(Some(handle)),
+            metrics,
+            status,
+        })
     }
 
     /// Enqueue a message for writing (non-blocking)
     ///
-    /// Returns `Ok(true)` if enqueued, `Ok(false)` if queue is full.
+    /// Returns `Ok(true)` if enqueued, `Ok(false)` if queue is full, and
+    /// [`Error::WriterUnavailable`] while the worker is restarting.
     pub fn enqueue(&self, message: Message) -> Result<bool> {
-        match self.tx.try_send(WriterCommand::Message(message)) {
+        match self.try_enqueue(message) {
             Ok(()) => Ok(true),
-            Err(mpsc::error::TrySendError::Full(_)) => {
-                warn!("MessageWriter queue full, dropping message");
-                Ok(false)
+            Err(Error::QueueFull) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Enqueue a message for writing, failing with [`Error::QueueFull`] if the
+    /// queue is full
+    #[instrument(
+        name = "publish",
+        skip_all,
+        fields(
+            session_id = %message.session_id,
+            event_type = %message.event_type,
+            traceparent = message.traceparent.as_deref(),
+        )
+    )]
+    pub fn try_enqueue(&self, message: Message) -> Result<()> {
+        self.check_available()?;
+        let message = with_current_trace(message);
+        let result = if message.priority == Priority::High {
+            self.priority_tx.try_send(message).map_err(|e| match e {
+                mpsc::error::TrySendError::Full(_) => {
+                    warn!("MessageWriter priority queue full, dropping message");
+                    Error::QueueFull
+                }
+                mpsc::error::TrySendError::Closed(_) => Error::Shutdown,
+            })
+        } else {
+            self.tx
+                .try_send(WriterCommand::Message(message))
+                .map_err(|e| match e {
+                    mpsc::error::TrySendError::Full(_) => {
+                        warn!("MessageWriter queue full, dropping message");
+                        Error::QueueFull
+                    }
+                    mpsc::error::TrySendError::Closed(_) => Error::Shutdown,
+                })
+        };
+
+        match &result {
+            Ok(()) => {
+                self.metrics.enqueued.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(Error::QueueFull) => {
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
             }
-            Err(mpsc::error::TrySendError::Closed(_)) => Err(Error::Shutdown),
+            Err(_) => {}
         }
+        result
     }
 
     /// Enqueue a message for writing (async, waits if queue is full)
+    #[instrument(
+        name = "publish",
+        skip_all,
+        fields(
+            session_id = %message.session_id,
+            event_type = %message.event_type,
+            traceparent = message.traceparent.as_deref(),
+        )
+    )]
     pub async fn enqueue_async(&self, message: Message) -> Result<()> {
-        self.tx
-            .send(WriterCommand::Message(message))
-            .await
-            .map_err(|_| Error::Shutdown)
+        self.check_available()?;
+        let message = with_current_trace(message);
+        if message.priority == Priority::High {
+            self.priority_tx
+                .send(message)
+                .await
+                .map_err(|_| Error::Shutdown)?;
+        } else {
+            self.tx
+                .send(WriterCommand::Message(message))
+                .await
+                .map_err(|_| Error::Shutdown)?;
+        }
+
+        self.metrics.enqueued.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Check if the background writer task is running
+    ///
+    /// False while a crashed worker is backing off before its restart.
+    pub fn is_alive(&self) -> bool {
+        self.state() == TaskState::Running
+            && self
+                .handle
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_ref()
+                .is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Lifecycle state of the worker task
+    pub fn state(&self) -> TaskState {
+        self.status.state()
+    }
+
+    /// When the worker task last crashed
+    pub fn last_failure_at(&self) -> Option<DateTime<Utc>> {
+        self.status.last_failure_at()
+    }
+
+    fn check_available(&self) -> Result<()> {
+        if self.state() == TaskState::BackingOff {
+            let error = self.status.last_error().unwrap_or_default();
+            return Err(Error::WriterUnavailable(error));
+        }
+        Ok(())
+    }
+
+    /// Snapshot queue depth and throughput counters
+    pub fn stats(&self) -> WriterStats {
+        let m = &self.metrics;
+        WriterStats {
+            queue_depth: self.tx.max_capacity() - self.tx.capacity(),
+            priority_queue_depth: self.priority_tx.max_capacity() - self.priority_tx.capacity(),
+            queue_capacity: self.tx.max_capacity(),
+            enqueued: m.enqueued.load(Ordering::Relaxed),
+            dropped: m.dropped.load(Ordering::Relaxed),
+            written: m.written.load(Ordering::Relaxed),
+            coalesced: m.coalesced.load(Ordering::Relaxed),
+            failed_batches: m.failed_batches.load(Ordering::Relaxed),
+            restarts: self.status.restarts(),
+            last_error: self.status.last_error(),
+            batch_size: m.batch_size.snapshot(),
+            write_latency_us: m.write_latency.snapshot(),
+        }
     }
 
     /// Flush all pending messages to the database
@@ -73,35 +230,69 @@ impl MessageWriter {
         rx.await.map_err(|_| Error::Shutdown)
     }
 
-    /// Shutdown the writer gracefully
-    pub async fn shutdown(self) -> Result<()> {
+    /// Stop accepting messages, write everything queued and wait for the
+    /// worker to exit
+    ///
+    /// Works through a shared reference, so callers still holding the writer
+    /// get [`Error::Shutdown`] afterwards. Calling it again is a no-op.
+    pub async fn close(&self) -> Result<()> {
+        let Some(handle) = self.handle.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return Ok(());
+        };
         info!("MessageWriter shutting down...");
 
         // Send shutdown command
         let _ = self.tx.send(WriterCommand::Shutdown).await;
 
         // Wait for worker to finish
-        self.handle
+        handle
             .await
             .map_err(|e| Error::Config(format!("Worker panicked: {}", e)))?;
 
         info!("MessageWriter shutdown complete");
         Ok(())
     }
+
+    /// Shutdown the writer gracefully
+    pub async fn shutdown(self) -> Result<()> {
+        self.close().await
+    }
 }
 
-async fn writer_loop(mut rx: mpsc::Receiver<WriterCommand>, db: Arc<DbPool>, batch_size: usize) {
+/// Fill in the `traceparent` of the current (`publish`) span if the caller
+/// didn't set one
+fn with_current_trace(mut message: Message) -> Message {
+    if message.traceparent.is_none() {
+        message.traceparent = trace_context::current();
+    }
+    message
+}
+
+async fn writer_loop(
+    rx: &mut mpsc::Receiver<WriterCommand>,
+    priority_rx: &mut mpsc::Receiver<Message>,
+    flush_waiters: &mut Vec<tokio::sync::oneshot::Sender<()>>,
+    db: Arc<DbPool>,
+    batch_size: usize,
+    metrics: Arc<WriterMetrics>,
+) {
     let mut batch = Vec::with_capacity(batch_size);
-    let mut flush_waiters: Vec<tokio::sync::oneshot::Sender<()>> = Vec::new();
+
+    // Waiters left by a panicked run; their batch was dropped
+    signal_flush_waiters(flush_waiters);
 
     loop {
-        // Wait for first message or command
-        let cmd = match rx.recv().await {
-            Some(cmd) => cmd,
-            None => {
-                debug!("Channel closed, exiting writer loop");
-                break;
-            }
+        // Wait for first message or command (priority lane first)
+        let cmd = tokio::select! {
+            biased;
+            Some(msg) = priority_rx.recv() => WriterCommand::Message(msg),
+            cmd = rx.recv() => match cmd {
+                Some(cmd) => cmd,
+                None => {
+                    debug!("Channel closed, exiting writer loop");
+                    break;
+                }
+            },
         };
 
         match cmd {
@@ -114,19 +305,24 @@ async fn writer_loop(mut rx: mpsc::Receiver<WriterCommand>, db: Arc<DbPool>, bat
             WriterCommand::Shutdown => {
                 debug!("Shutdown command received");
                 // Drain remaining messages
-                drain_remaining(&mut rx, &mut batch, &mut flush_waiters);
+                drain_remaining(rx, priority_rx, &mut batch, flush_waiters);
                 // Write final batch
                 if !batch.is_empty() {
-                    write_batch(&db, &mut batch).await;
+                    write_batch(&db, &mut batch, &metrics).await;
                 }
                 // Signal all flush waiters
-                signal_flush_waiters(&mut flush_waiters);
+                signal_flush_waiters(flush_waiters);
                 break;
             }
         }
 
-        // Try to fill batch (non-blocking)
+        // Try to fill batch (non-blocking), draining the priority lane first
         while batch.len() < batch_size {
+            if let Ok(msg) = priority_rx.try_recv() {
+                batch.push(msg);
+                continue;
+            }
+
             match rx.try_recv() {
                 Ok(WriterCommand::Message(msg)) => {
                     batch.push(msg);
@@ -136,11 +332,11 @@ async fn writer_loop(mut rx: mpsc::Receiver<WriterCommand>, db: Arc<DbPool>, bat
                     break; // Stop filling, write now
                 }
                 Ok(WriterCommand::Shutdown) => {
-                    drain_remaining(&mut rx, &mut batch, &mut flush_waiters);
+                    drain_remaining(rx, priority_rx, &mut batch, flush_waiters);
                     if !batch.is_empty() {
-                        write_batch(&db, &mut batch).await;
+                        write_batch(&db, &mut batch, &metrics).await;
                     }
-                    signal_flush_waiters(&mut flush_waiters);
+                    signal_flush_waiters(flush_waiters);
                     return;
                 }
                 Err(_) => break, // No more messages available
@@ -149,19 +345,26 @@ async fn writer_loop(mut rx: mpsc::Receiver<WriterCommand>, db: Arc<DbPool>, bat
 
         // Write batch if non-empty
         if !batch.is_empty() {
-            write_batch(&db, &mut batch).await;
+            write_batch(&db, &mut batch, &metrics).await;
         }
 
         // Signal flush waiters
-        signal_flush_waiters(&mut flush_waiters);
+        signal_flush_waiters(flush_waiters);
     }
 }
 
 fn drain_remaining(
     rx: &mut mpsc::Receiver<WriterCommand>,
+    priority_rx: &mut mpsc::Receiver<Message>,
     batch: &mut Vec<Message>,
     flush_waiters: &mut Vec<tokio::sync::oneshot::Sender<()>>,
 ) {
+    // Later sends fail with `Error::Shutdown` instead of being lost
+    rx.close();
+    priority_rx.close();
+    while let Ok(msg) = priority_rx.try_recv() {
+        batch.push(msg);
+    }
     while let Ok(cmd) = rx.try_recv() {
         match cmd {
             WriterCommand::Message(msg) => batch.push(msg),
@@ -171,16 +374,36 @@ fn drain_remaining(
     }
 }
 
-async fn write_batch(db: &DbPool, batch: &mut Vec<Message>) {
+#[instrument(
+    name = "write_batch",
+    skip_all,
+    fields(backend = db.backend_name(), batch_size = field::Empty)
+)]
+async fn write_batch(db: &DbPool, batch: &mut Vec<Message>, metrics: &WriterMetrics) {
+    let coalesced = coalesce(batch);
+    if coalesced > 0 {
+        debug!("Coalesced {} superseded messages", coalesced);
+        metrics
+            .coalesced
+            .fetch_add(coalesced as u64, Ordering::Relaxed);
+    }
+
     let count = batch.len();
+    Span::current().record("batch_size", count);
     debug!("Writing batch of {} messages", count);
 
+    // High priority first; stable sort keeps per-session order within a priority
+    batch.sort_by_key(|msg| Reverse(msg.priority));
+
+    let started = Instant::now();
     match db.insert_batch(batch).await {
         Ok(()) => {
             debug!("Successfully wrote {} messages", count);
+            metrics.record_batch(count, started.elapsed(), true);
         }
         Err(e) => {
             error!("Failed to write batch: {}", e);
+            metrics.record_batch(count, started.elapsed(), false);
             // TODO: Implement retry logic or dead letter queue
         }
     }
@@ -188,6 +411,32 @@ async fn write_batch(db: &DbPool, batch: &mut Vec<Message>) {
     batch.clear();
 }
 
+/// Keep only the last message per `(session_id, coalesce_key)`
+///
+/// Returns the number of messages dropped.
+fn coalesce(batch: &mut Vec<Message>) -> usize {
+    let before = batch.len();
+
+    let keep: Vec<bool> = {
+        let mut seen = HashSet::new();
+        let mut keep: Vec<bool> = batch
+            .iter()
+            .rev()
+            .map(|msg| match &msg.coalesce_key {
+                Some(key) => seen.insert((msg.session_id.as_str(), key.as_str())),
+                None => true,
+            })
+            .collect();
+        keep.reverse();
+        keep
+    };
+
+    let mut keep = keep.into_iter();
+    batch.retain(|_| keep.next().unwrap_or(true));
+
+    before - batch.len()
+}
+
 fn signal_flush_waiters(waiters: &mut Vec<tokio::sync::oneshot::Sender<()>>) {
     for waiter in waiters.drain(..) {
         let _ = waiter.send(());
@@ -249,4 +498,161 @@ mod tests {
 
         writer.shutdown().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_writer_priority_lane() {
+        let db = create_test_db().await;
+        let config = Config::new("sqlite::memory:").batch_size(100);
+
+        let writer = MessageWriter::new(db.clone(), &config).await.unwrap();
+
+        // Queue bulk traffic, then a control event behind it
+        for i in 0..20 {
+            let msg = Message::new("session-1", "progress", format!(r#"{{"i":{}}}"#, i));
+            assert!(writer.enqueue(msg).unwrap());
+        }
+        let closed =
+            Message::new("session-1", "connection_closed", "{}").with_priority(Priority::High);
+        assert!(writer.enqueue(closed).unwrap());
+
+        writer.flush().await.unwrap();
+
+        let messages = db.fetch_after("session-1", 0, 100).await.unwrap();
+        assert_eq!(messages.len(), 21);
+        assert_eq!(messages[0].event_type, "connection_closed");
+
+        // Normal messages keep their relative order
+        let normal: Vec<_> = messages[1..].iter().map(|m| m.data.clone()).collect();
+        let expected: Vec<_> = (0..20).map(|i| format!(r#"{{"i":{}}}"#, i)).collect();
+        assert_eq!(normal, expected);
+
+        writer.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_close_through_shared_reference() {
+        let db = create_test_db().await;
+        let config = Config::new("sqlite::memory:").batch_size(10);
+
+        let writer = Arc::new(MessageWriter::new(db.clone(), &config).await.unwrap());
+        let other = writer.clone();
+
+        for _ in 0..3 {
+            assert!(
+                writer
+                    .enqueue(Message::new("session-1", "message", "{}"))
+                    .unwrap()
+            );
+        }
+        writer.close().await.unwrap();
+
+        assert_eq!(db.fetch_after("session-1", 0, 100).await.unwrap().len(), 3);
+        assert!(!other.is_alive());
+        assert!(matches!(
+            other.try_enqueue(Message::new("session-1", "message", "{}")),
+            Err(Error::Shutdown)
+        ));
+        other.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_unavailable_while_backing_off() {
+        let db = create_test_db().await;
+        let writer = MessageWriter::new(db, &Config::new("sqlite::memory:"))
+            .await
+            .unwrap();
+        assert!(writer.is_alive());
+
+        writer.status.record_failure("panicked: boom".to_string());
+        assert!(!writer.is_alive());
+        assert_eq!(writer.state(), TaskState::BackingOff);
+        assert!(writer.last_failure_at().is_some());
+        assert!(matches!(
+            writer.enqueue(Message::new("session-1", "message", "{}")),
+            Err(Error::WriterUnavailable(ref e)) if e == "panicked: boom"
+        ));
+    }
+
+    #[test]
+    fn test_coalesce_keeps_last_per_session_and_key() {
+        let mut batch = vec![
+            Message::new("s1", "progress", "1").with_coalesce_key("tok"),
+            Message::new("s1", "message", "a"),
+            Message::new("s2", "progress", "x").with_coalesce_key("tok"),
+            Message::new("s1", "progress", "2").with_coalesce_key("tok"),
+            Message::new("s1", "message", "b"),
+            Message::new("s1", "progress", "3").with_coalesce_key("tok"),
+        ];
+
+        assert_eq!(coalesce(&mut batch), 2);
+
+        let data: Vec<_> = batch.iter().map(|m| m.data.as_str()).collect();
+        assert_eq!(data, vec!["a", "x", "b", "3"]);
+    }
+
+    #[tokio::test]
+    async fn test_writer_coalescing() {
+        let db = create_test_db().await;
+        let config = Config::new("sqlite::memory:").batch_size(100);
+
+        let writer = MessageWriter::new(db.clone(), &config).await.unwrap();
+
+        for i in 0..50 {
+            let msg = Message::new("session-1", "progress", format!(r#"{{"progress":{}}}"#, i))
+                .with_coalesce_key("tok-1");
+            assert!(writer.enqueue(msg).unwrap());
+        }
+        assert!(
+            writer
+                .enqueue(Message::new("session-1", "message", "{}"))
+                .unwrap()
+        );
+
+        writer.flush().await.unwrap();
+
+        let messages = db.fetch_after("session-1", 0, 100).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].data, r#"{"progress":49}"#);
+        assert_eq!(messages[1].event_type, "message");
+
+        writer.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_writer_stats() {
+        let db = create_test_db().await;
+        let config = Config::new("sqlite::memory:")
+            .batch_size(100)
+            .max_queue_size(3);
+
+        let writer = MessageWriter::new(db.clone(), &config).await.unwrap();
+
+        for _ in 0..4 {
+            writer
+                .enqueue(Message::new("session-1", "message", "{}"))
+                .unwrap();
+        }
+
+        assert!(matches!(
+            writer.try_enqueue(Message::new("session-1", "message", "{}")),
+            Err(Error::QueueFull)
+        ));
+
+        let stats = writer.stats();
+        assert_eq!(stats.queue_depth, 3);
+        assert_eq!(stats.queue_capacity, 3);
+        assert_eq!(stats.enqueued, 3);
+        assert_eq!(stats.dropped, 2);
+
+        writer.flush().await.unwrap();
+
+        let stats = writer.stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.written, 3);
+        assert_eq!(stats.failed_batches, 0);
+        assert_eq!(stats.batch_size.count, 1);
+        assert_eq!(stats.batch_size.sum, 3);
+
+        writer.shutdown().await.unwrap();
+    }
 }
@@ -0,0 +1,128 @@
+//! Process-wide engine shared by all Ruby threads
+//!
+//! The engine remembers the PID that created it. After a fork (Puma or
+//! Unicorn workers) the child sees a different PID and builds a fresh engine
+//! from the same [`Config`] on first use. The parent's engine is leaked in the
+//! child: its tasks and connections belong to the parent process.
+
+use crate::errors::{not_initialized, ruby_error};
+use crate::{gvl, runtime};
+use magnus::{Error, Ruby};
+use solid_mcp_core::{Config, PubSub};
+use std::process;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::info;
+
+struct Engine {
+    pubsub: Arc<PubSub>,
+    config: Config,
+    pid: u32,
+}
+
+impl Engine {
+    fn new(pubsub: Arc<PubSub>, config: Config) -> Self {
+        Self {
+            pubsub,
+            config,
+            pid: process::id(),
+        }
+    }
+}
+
+// Locks are only held briefly and never while the GVL is released, so a
+// thread waiting on the lock cannot block one that needs the GVL back.
+static ENGINE: RwLock<Option<Engine>> = RwLock::new(None);
+
+fn read() -> RwLockReadGuard<'static, Option<Engine>> {
+    ENGINE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write() -> RwLockWriteGuard<'static, Option<Engine>> {
+    ENGINE.write().unwrap_or_else(|e| e.into_inner())
+}
+
+fn start(ruby: &Ruby, config: Config) -> Result<Arc<PubSub>, Error> {
+    let pubsub = gvl::block_on(ruby, runtime::get(), PubSub::new(config))?.map_err(ruby_error)?;
+    Ok(Arc::new(pubsub))
+}
+
+/// Start the engine, replacing (and shutting down) any running one
+pub fn init(ruby: &Ruby, config: Config) -> Result<(), Error> {
+    let pubsub = start(ruby, config.clone())?;
+    let previous = write().replace(Engine::new(pubsub, config));
+
+    match previous {
+        Some(previous) if previous.pid == process::id() => stop(ruby, previous.pubsub),
+        Some(previous) => {
+            std::mem::forget(previous);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Get the engine, re-initializing it if the process has forked
+pub fn get(ruby: &Ruby) -> Result<Arc<PubSub>, Error> {
+    let config = {
+        let guard = read();
+        let engine = guard.as_ref().ok_or_else(not_initialized)?;
+        if engine.pid == process::id() {
+            return Ok(engine.pubsub.clone());
+        }
+        engine.config.clone()
+    };
+
+    info!(
+        "Fork detected (pid {}), re-initializing engine",
+        process::id()
+    );
+    let pubsub = start(ruby, config.clone())?;
+
+    let mut guard = write();
+    match guard.as_mut() {
+        // Another thread re-initialized first
+        Some(engine) if engine.pid == process::id() => {
+            let current = engine.pubsub.clone();
+            drop(guard);
+            stop(ruby, pubsub)?;
+            Ok(current)
+        }
+        Some(engine) => {
+            let stale = std::mem::replace(engine, Engine::new(pubsub.clone(), config));
+            std::mem::forget(stale);
+            Ok(pubsub)
+        }
+        // Shut down while we were starting
+        None => {
+            drop(guard);
+            stop(ruby, pubsub)?;
+            Err(not_initialized())
+        }
+    }
+}
+
+/// Check if an engine has been initialized in this process or its parent
+pub fn is_initialized() -> bool {
+    read().is_some()
+}
+
+/// Shut down the engine, flushing pending messages
+pub fn shutdown(ruby: &Ruby) -> Result<(), Error> {
+    let Some(engine) = write().take() else {
+        return Ok(());
+    };
+
+    if engine.pid != process::id() {
+        std::mem::forget(engine);
+        return Ok(());
+    }
+    stop(ruby, engine.pubsub)
+}
+
+/// Stop the engine even while other threads hold it
+///
+/// Threads blocked in `receive` are woken and later calls fail, so the
+/// writer is always flushed; a flush error is raised in Ruby.
+fn stop(ruby: &Ruby, pubsub: Arc<PubSub>) -> Result<(), Error> {
+    gvl::block_on(ruby, runtime::get(), pubsub.close())?.map_err(ruby_error)
+}
@@ -0,0 +1,166 @@
+//! Ruby exception classes for core errors
+//!
+//! All classes live under `SolidMCPNative` and inherit from
+//! `SolidMCPNative::Error < StandardError`:
+//!
+//! ```text
+//! Error
+//! ├── NotInitialized
+//! ├── AlreadySubscribed
+//! ├── QueueFull
+//! ├── ShutdownError
+//! └── DatabaseError            (#sqlstate, #db_message)
+//!     ├── ConnectionLost
+//!     ├── PoolTimeout
+//!     ├── ConstraintViolation
+//!     └── SchemaMismatch
+//! ```
+
+use magnus::value::Lazy;
+use magnus::{Attr, Error, ExceptionClass, Module, RModule, ReprValue, Ruby, Value};
+use solid_mcp_core::Error as CoreError;
+
+fn module(ruby: &Ruby) -> RModule {
+    ruby.define_module("SolidMCPNative").unwrap()
+}
+
+static ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("Error", ruby.exception_standard_error())
+        .unwrap()
+});
+
+static DATABASE_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    let class = module(ruby)
+        .define_error("DatabaseError", ruby.get_inner(&ERROR))
+        .unwrap();
+    class.define_attr("sqlstate", Attr::Read).unwrap();
+    class.define_attr("db_message", Attr::Read).unwrap();
+    class
+});
+
+static CONNECTION_LOST: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("ConnectionLost", ruby.get_inner(&DATABASE_ERROR))
+        .unwrap()
+});
+
+static POOL_TIMEOUT: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("PoolTimeout", ruby.get_inner(&DATABASE_ERROR))
+        .unwrap()
+});
+
+static CONSTRAINT_VIOLATION: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("ConstraintViolation", ruby.get_inner(&DATABASE_ERROR))
+        .unwrap()
+});
+
+static SCHEMA_MISMATCH: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("SchemaMismatch", ruby.get_inner(&DATABASE_ERROR))
+        .unwrap()
+});
+
+static QUEUE_FULL: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("QueueFull", ruby.get_inner(&ERROR))
+        .unwrap()
+});
+
+static NOT_INITIALIZED: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("NotInitialized", ruby.get_inner(&ERROR))
+        .unwrap()
+});
+
+static SHUTDOWN_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("ShutdownError", ruby.get_inner(&ERROR))
+        .unwrap()
+});
+
+static ALREADY_SUBSCRIBED: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    module(ruby)
+        .define_error("AlreadySubscribed", ruby.get_inner(&ERROR))
+        .unwrap()
+});
+
+/// Define the exception classes so they exist before the first error
+pub fn define(ruby: &Ruby) {
+    for class in [
+        &ERROR,
+        &DATABASE_ERROR,
+        &CONNECTION_LOST,
+        &POOL_TIMEOUT,
+        &CONSTRAINT_VIOLATION,
+        &SCHEMA_MISMATCH,
+        &QUEUE_FULL,
+        &NOT_INITIALIZED,
+        &SHUTDOWN_ERROR,
+        &ALREADY_SUBSCRIBED,
+    ] {
+        Lazy::force(class, ruby);
+    }
+}
+
+/// Convert a core error into the matching Ruby exception
+///
+/// Database errors carry the driver message and SQLSTATE code as the
+/// `db_message` and `sqlstate` attributes.
+pub fn ruby_error(err: CoreError) -> Error {
+    let ruby = Ruby::get().unwrap();
+    let class = match &err {
+        CoreError::Database(_) => &DATABASE_ERROR,
+        CoreError::ConnectionLost(_) => &CONNECTION_LOST,
+        CoreError::PoolTimeout => &POOL_TIMEOUT,
+        CoreError::Constraint(_) => &CONSTRAINT_VIOLATION,
+        CoreError::SchemaMismatch(_) => &SCHEMA_MISMATCH,
+        CoreError::QueueFull => &QUEUE_FULL,
+        CoreError::NotInitialized => &NOT_INITIALIZED,
+        CoreError::AlreadySubscribed(_) => &ALREADY_SUBSCRIBED,
+        CoreError::Shutdown | CoreError::ChannelSend | CoreError::ChannelRecv => &SHUTDOWN_ERROR,
+        _ => &ERROR,
+    };
+
+    let exception = match ruby.get_inner(class).new_instance((err.to_string(),)) {
+        Ok(exception) => exception,
+        Err(e) => return e,
+    };
+    if let Some(db_message) = database_message(&err)
+        && let Err(e) = set_database_attrs(exception.as_value(), err.sqlstate(), db_message)
+    {
+        return e;
+    }
+    exception.into()
+}
+
+/// Driver message for database errors, falling back to the sqlx error text
+fn database_message(err: &CoreError) -> Option<String> {
+    match err {
+        CoreError::Database(source)
+        | CoreError::ConnectionLost(source)
+        | CoreError::Constraint(source)
+        | CoreError::SchemaMismatch(source) => Some(
+            err.database_message()
+                .map_or_else(|| source.to_string(), str::to_owned),
+        ),
+        _ => None,
+    }
+}
+
+fn set_database_attrs(
+    exception: Value,
+    sqlstate: Option<String>,
+    db_message: String,
+) -> Result<(), Error> {
+    let _: Value = exception.funcall("instance_variable_set", ("@sqlstate", sqlstate))?;
+    let _: Value = exception.funcall("instance_variable_set", ("@db_message", db_message))?;
+    Ok(())
+}
+
+/// Error raised when the engine has not been initialized
+pub fn not_initialized() -> Error {
+    ruby_error(CoreError::NotInitialized)
+}
@@ -0,0 +1,107 @@
+//! Running Rust code without the Ruby GVL
+//!
+//! Blocking FFI calls release the GVL so other Ruby threads (e.g. Puma
+//! workers) keep running while we wait on the database.
+
+use magnus::{Error, Ruby};
+use std::ffi::c_void;
+use std::future::Future;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::pin;
+use std::ptr;
+use tokio::runtime::Runtime;
+use tokio::sync::Notify;
+
+/// Run `func` with the GVL released
+///
+/// `unblock` is called from another thread when Ruby needs to interrupt the
+/// current thread (signals, `Thread#kill`, `Thread#raise`); it must make `func`
+/// return promptly. `func` must not touch any Ruby object.
+///
+/// Returns `None` without running `func` if an interrupt is already pending.
+/// Ruby never raises in here (`RB_NOGVL_INTR_FAIL`): a raise would longjmp
+/// over these frames and skip their destructors. Callers handle interrupts
+/// with [`Ruby::thread_check_ints`] once their own values are dropped.
+pub fn without_gvl<F, R, U>(func: F, unblock: U) -> Option<R>
+where
+    F: FnOnce() -> R,
+    U: Fn() + Sync,
+{
+    struct Call<F, R> {
+        func: Option<F>,
+        result: Option<std::thread::Result<R>>,
+    }
+
+    unsafe extern "C" fn call<F, R>(data: *mut c_void) -> *mut c_void
+    where
+        F: FnOnce() -> R,
+    {
+        let call = unsafe { &mut *(data as *mut Call<F, R>) };
+        let func = call.func.take().unwrap();
+        call.result = Some(panic::catch_unwind(AssertUnwindSafe(func)));
+        ptr::null_mut()
+    }
+
+    unsafe extern "C" fn unblock_fn<U>(data: *mut c_void)
+    where
+        U: Fn(),
+    {
+        let unblock = unsafe { &*(data as *const U) };
+        let _ = panic::catch_unwind(AssertUnwindSafe(unblock));
+    }
+
+    let mut data = Call {
+        func: Some(func),
+        result: None,
+    };
+
+    unsafe {
+        rb_sys::rb_nogvl(
+            Some(call::<F, R>),
+            &mut data as *mut Call<F, R> as *mut c_void,
+            Some(unblock_fn::<U>),
+            &unblock as *const U as *mut c_void,
+            rb_sys::RB_NOGVL_INTR_FAIL as _,
+        );
+    }
+
+    match data.result? {
+        Ok(result) => Some(result),
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
+
+/// Run `future` to completion on `runtime` with the GVL released
+///
+/// When Ruby interrupts the thread (signals, `Thread#kill`, `Thread#raise`,
+/// timer and GC requests), pending interrupts are handled with the GVL held
+/// after the blocking call returns. If one raises, the future is dropped
+/// and the exception propagates;
+/// otherwise the same future is resumed, so a trap handler that returns
+/// doesn't cancel a flush or shutdown halfway.
+pub fn block_on<F>(ruby: &Ruby, runtime: &Runtime, future: F) -> Result<F::Output, Error>
+where
+    F: Future,
+{
+    let mut future = pin!(future);
+    loop {
+        let interrupted = Notify::new();
+        let output = without_gvl(
+            || {
+                runtime.block_on(async {
+                    tokio::select! {
+                        output = &mut future => Some(output),
+                        _ = interrupted.notified() => None,
+                    }
+                })
+            },
+            || interrupted.notify_one(),
+        );
+
+        // Interrupted while blocked, or before the call started
+        match output.flatten() {
+            Some(output) => return Ok(output),
+            None => ruby.thread_check_ints()?,
+        }
+    }
+}
@@ -2,41 +2,83 @@
 //!
 //! Exposes the Rust pub/sub engine to Ruby via Magnus.
 
-use magnus::{Error, Ruby, function};
-use solid_mcp_core::{Config, PubSub};
-use std::cell::RefCell;
-use std::sync::{Arc, OnceLock};
+mod engine;
+mod errors;
+mod gvl;
+mod options;
+mod runtime;
+mod subscription;
+
+use errors::ruby_error;
+use magnus::scan_args::{get_kwargs, scan_args};
+use magnus::{Error, RArray, RHash, Ruby, Symbol, Value, function};
+use runtime::{Flavor, RuntimeOptions};
+use solid_mcp_core::metrics::HistogramSnapshot;
+use solid_mcp_core::{Config, Message, Stats};
+use std::sync::Arc;
 use std::time::Duration;
-use tokio::runtime::Runtime;
+use subscription::Mailbox;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
-// Global Tokio runtime
-static RUNTIME: OnceLock<Runtime> = OnceLock::new();
-
-// Thread-local PubSub instance (each Ruby thread gets its own)
-thread_local! {
-    static PUBSUB: RefCell<Option<Arc<PubSub>>> = const { RefCell::new(None) };
-}
+/// Configure the Tokio runtime before first use
+///
+/// Accepts `flavor:` (`:multi_thread` or `:current_thread`),
+/// `worker_threads:`, `thread_name:` and `max_blocking_threads:`.
+fn configure_runtime(ruby: &Ruby, args: &[Value]) -> Result<bool, Error> {
+    let args = scan_args::<(), (), (), (), RHash, ()>(args)?;
+    let kwargs =
+        get_kwargs::<_, (), (Option<Symbol>, Option<usize>, Option<String>, Option<usize>), ()>(
+            args.keywords,
+            &[],
+            &[
+                "flavor",
+                "worker_threads",
+                "thread_name",
+                "max_blocking_threads",
+            ],
+        )?;
+    let (flavor, worker_threads, thread_name, max_blocking_threads) = kwargs.optional;
+
+    let arg_error = |msg: String| Error::new(ruby.exception_arg_error(), msg);
+    let mut options = RuntimeOptions::default();
+    if let Some(flavor) = flavor {
+        options.flavor = match flavor.name()?.as_ref() {
+            "multi_thread" => Flavor::MultiThread,
+            "current_thread" => Flavor::CurrentThread,
+            other => return Err(arg_error(format!("unknown runtime flavor: {}", other))),
+        };
+    }
+    if let Some(worker_threads) = worker_threads {
+        if worker_threads == 0 {
+            return Err(arg_error("worker_threads must be positive".to_string()));
+        }
+        options.worker_threads = worker_threads;
+    }
+    if let Some(thread_name) = thread_name {
+        options.thread_name = thread_name;
+    }
+    if let Some(max_blocking_threads) = max_blocking_threads {
+        if max_blocking_threads == 0 {
+            return Err(arg_error(
+                "max_blocking_threads must be positive".to_string(),
+            ));
+        }
+        options.max_blocking_threads = max_blocking_threads;
+    }
 
-fn get_runtime() -> &'static Runtime {
-    RUNTIME.get_or_init(|| {
-        tokio::runtime::Builder::new_multi_thread()
-            .enable_all()
-            .worker_threads(4)
-            .thread_name("solid-mcp-worker")
-            .build()
-            .expect("Failed to create Tokio runtime")
-    })
+    runtime::configure(options).map_err(ruby_error)?;
+    Ok(true)
 }
 
-/// Helper to create a runtime error
-fn runtime_error(msg: impl Into<String>) -> Error {
-    Error::new(Ruby::get().unwrap().exception_runtime_error(), msg.into())
-}
+/// Initialize the pub/sub engine with a database URL and keyword options
+///
+/// See [`options`] for the accepted keys.
+fn init_engine(ruby: &Ruby, args: &[Value]) -> Result<bool, Error> {
+    let args = scan_args::<(String,), (), (), (), RHash, ()>(args)?;
+    let (database_url,) = args.required;
+    let config = options::config_from_hash(ruby, database_url, args.keywords)?;
 
-/// Initialize the pub/sub engine with a database URL
-fn init_engine(database_url: String) -> Result<bool, Error> {
     // Initialize tracing if DEBUG env var is set
     if std::env::var("DEBUG_SOLID_MCP").is_ok() {
         let subscriber = FmtSubscriber::builder()
@@ -45,133 +87,165 @@ fn init_engine(database_url: String) -> Result<bool, Error> {
         let _ = tracing::subscriber::set_global_default(subscriber);
     }
 
-    let rt = get_runtime();
-
-    let config = Config::new(&database_url);
-
-    let pubsub = rt
-        .block_on(async { PubSub::new(config).await })
-        .map_err(|e| runtime_error(e.to_string()))?;
-
-    PUBSUB.with(|ps| {
-        *ps.borrow_mut() = Some(Arc::new(pubsub));
-    });
-
+    engine::init(ruby, config)?;
     Ok(true)
 }
 
 /// Initialize with custom configuration
+///
+/// Positional form kept for compatibility; prefer `init(url, **options)`.
 fn init_engine_with_config(
+    ruby: &Ruby,
     database_url: String,
     batch_size: usize,
     polling_interval_ms: u64,
     max_queue_size: usize,
 ) -> Result<bool, Error> {
-    let rt = get_runtime();
-
     let config = Config::new(&database_url)
         .batch_size(batch_size)
         .polling_interval(Duration::from_millis(polling_interval_ms))
         .max_queue_size(max_queue_size);
 
-    let pubsub = rt
-        .block_on(async { PubSub::new(config).await })
-        .map_err(|e| runtime_error(e.to_string()))?;
+    engine::init(ruby, config)?;
+    Ok(true)
+}
+
+/// Broadcast a message to a session (non-blocking)
+fn broadcast(
+    ruby: &Ruby,
+    session_id: String,
+    event_type: String,
+    data: String,
+) -> Result<bool, Error> {
+    let pubsub = engine::get(ruby)?;
+
+    pubsub
+        .broadcast(&session_id, &event_type, &data)
+        .map_err(ruby_error)
+}
+
+/// Broadcast a message carrying the caller's W3C trace context (non-blocking)
+fn broadcast_traced(
+    ruby: &Ruby,
+    session_id: String,
+    event_type: String,
+    data: String,
+    traceparent: Option<String>,
+) -> Result<bool, Error> {
+    let pubsub = engine::get(ruby)?;
 
-    PUBSUB.with(|ps| {
-        *ps.borrow_mut() = Some(Arc::new(pubsub));
-    });
+    let mut message = Message::new(session_id, event_type, data);
+    message.traceparent = traceparent;
 
+    pubsub.publish(message).map_err(ruby_error)
+}
+
+/// Subscribe to a session, calling the block with each message Hash
+///
+/// The block runs on a dedicated Ruby thread. Up to `max_queue_size`
+/// messages wait for a slow block; older ones are dropped beyond that.
+fn subscribe(ruby: &Ruby, session_id: String) -> Result<bool, Error> {
+    let block = ruby.block_proc()?;
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    let mailbox = Arc::new(Mailbox::new(pubsub.config().max_queue_size));
+    let callback = subscription::callback(mailbox.clone());
+    gvl::block_on(ruby, rt, pubsub.subscribe(&session_id, callback))?.map_err(ruby_error)?;
+
+    subscription::spawn_dispatcher(ruby, &session_id, block, mailbox)?;
     Ok(true)
 }
 
-/// Broadcast a message to a session (non-blocking)
-fn broadcast(session_id: String, event_type: String, data: String) -> Result<bool, Error> {
-    PUBSUB.with(|ps| {
-        let ps = ps.borrow();
-        let pubsub = ps.as_ref().ok_or_else(|| {
-            runtime_error("Engine not initialized")
-        })?;
+/// Unsubscribe from a session; its dispatcher thread exits once drained
+fn unsubscribe(ruby: &Ruby, session_id: String) -> Result<bool, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    gvl::block_on(ruby, rt, pubsub.unsubscribe(&session_id))?.map_err(ruby_error)?;
+
+    Ok(true)
+}
 
-        pubsub
-            .broadcast(&session_id, &event_type, &data)
-            .map_err(|e| runtime_error(e.to_string()))
-    })
+/// Wait for messages for a session without holding the GVL
+///
+/// Returns an Array of message Hashes, empty if nothing arrived within
+/// `timeout_ms` (or `max_wait_time` when nil). The wait is capped at
+/// `max_wait_time`.
+///
+/// The first call for a session subscribes it and buffers messages until
+/// they are received, up to `max_queue_size`; beyond that the oldest are
+/// dropped and counted in `stats[:inbox_dropped]`. The subscription, with
+/// its polling or LISTEN connection, stays alive until
+/// `SolidMCPNative.unsubscribe` is called, so call it when the client goes
+/// away (e.g. in an `ensure` around an SSE loop). Raises `AlreadySubscribed`
+/// if the session already has a block subscription, and
+/// `SolidMCPNative::Error` if it is unsubscribed while waiting.
+fn receive(ruby: &Ruby, session_id: String, timeout_ms: Option<u64>) -> Result<RArray, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    let timeout = timeout_ms.map_or(Duration::MAX, Duration::from_millis);
+    let messages =
+        gvl::block_on(ruby, rt, pubsub.receive(&session_id, timeout))?.map_err(ruby_error)?;
+
+    let array = ruby.ary_new_capa(messages.len());
+    for message in &messages {
+        array.push(subscription::message_to_hash(ruby, message)?)?;
+    }
+    Ok(array)
 }
 
 /// Flush all pending messages to the database
-fn flush() -> Result<bool, Error> {
-    let rt = get_runtime();
-
-    PUBSUB.with(|ps| {
-        let ps = ps.borrow();
-        let pubsub = ps.as_ref().ok_or_else(|| {
-            runtime_error("Engine not initialized")
-        })?;
+fn flush(ruby: &Ruby) -> Result<bool, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
 
-        rt.block_on(async { pubsub.flush().await })
-            .map_err(|e| runtime_error(e.to_string()))?;
+    gvl::block_on(ruby, rt, pubsub.flush())?.map_err(ruby_error)?;
 
-        Ok(true)
-    })
+    Ok(true)
 }
 
 /// Mark messages as delivered
-fn mark_delivered(ids: Vec<i64>) -> Result<bool, Error> {
-    let rt = get_runtime();
-
-    PUBSUB.with(|ps| {
-        let ps = ps.borrow();
-        let pubsub = ps.as_ref().ok_or_else(|| {
-            runtime_error("Engine not initialized")
-        })?;
+fn mark_delivered(ruby: &Ruby, ids: Vec<i64>) -> Result<bool, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
 
-        rt.block_on(async { pubsub.mark_delivered(&ids).await })
-            .map_err(|e| runtime_error(e.to_string()))?;
+    gvl::block_on(ruby, rt, pubsub.mark_delivered(&ids))?.map_err(ruby_error)?;
 
-        Ok(true)
-    })
+    Ok(true)
 }
 
 /// Cleanup old messages
 /// Returns [delivered_count, undelivered_count]
-fn cleanup() -> Result<Vec<u64>, Error> {
-    let rt = get_runtime();
-
-    PUBSUB.with(|ps| {
-        let ps = ps.borrow();
-        let pubsub = ps.as_ref().ok_or_else(|| {
-            runtime_error("Engine not initialized")
-        })?;
+fn cleanup(ruby: &Ruby) -> Result<Vec<u64>, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
 
-        let (delivered, undelivered) = rt
-            .block_on(async { pubsub.cleanup().await })
-            .map_err(|e| runtime_error(e.to_string()))?;
+    let (delivered, undelivered) =
+        gvl::block_on(ruby, rt, pubsub.cleanup())?.map_err(ruby_error)?;
 
-        Ok(vec![delivered, undelivered])
-    })
+    Ok(vec![delivered, undelivered])
 }
 
 /// Shutdown the pub/sub engine
-fn shutdown() -> Result<bool, Error> {
-    let rt = get_runtime();
-
-    PUBSUB.with(|ps| {
-        let mut ps = ps.borrow_mut();
-        if let Some(pubsub) = ps.take() {
-            // Try to get exclusive access
-            match Arc::try_unwrap(pubsub) {
-                Ok(pubsub) => {
-                    let _ = rt.block_on(async { pubsub.shutdown().await });
-                }
-                Err(_) => {
-                    // Other references exist, can't fully shutdown
-                    tracing::warn!("Cannot fully shutdown: other references exist");
-                }
-            }
-        }
-        Ok(true)
-    })
+fn shutdown(ruby: &Ruby) -> Result<bool, Error> {
+    engine::shutdown(ruby)?;
+    Ok(true)
+}
+
+/// Rebuild the runtime and engine in a forked child
+///
+/// Forks are also detected lazily on the next call; calling this from an
+/// `on_worker_boot`/`after_fork` hook moves the reconnect out of the first
+/// request. Messages still queued in the parent are not carried over to the
+/// child; the parent flushes them.
+fn after_fork(ruby: &Ruby) -> Result<bool, Error> {
+    runtime::get();
+    if engine::is_initialized() {
+        engine::get(ruby)?;
+    }
+    Ok(true)
 }
 
 /// Get the library version
@@ -181,44 +255,173 @@ fn version() -> &'static str {
 
 /// Check if the engine is initialized
 fn initialized() -> bool {
-    PUBSUB.with(|ps| ps.borrow().is_some())
+    engine::is_initialized()
 }
 
 /// Get subscription count
-fn subscription_count() -> Result<usize, Error> {
-    let rt = get_runtime();
+fn subscription_count(ruby: &Ruby) -> Result<usize, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    gvl::block_on(ruby, rt, pubsub.subscription_count())
+}
 
-    PUBSUB.with(|ps| {
-        let ps = ps.borrow();
-        let pubsub = ps.as_ref().ok_or_else(|| {
-            runtime_error("Engine not initialized")
-        })?;
+/// Get engine metrics as a Hash
+fn stats(ruby: &Ruby) -> Result<RHash, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    let stats = gvl::block_on(ruby, rt, pubsub.stats())?.map_err(ruby_error)?;
+
+    stats_to_hash(ruby, &stats)
+}
+
+/// Get an engine health report as a Hash (for readiness probes)
+fn health(ruby: &Ruby) -> Result<RHash, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    let health = gvl::block_on(ruby, rt, pubsub.health())?;
+
+    let db = ruby.hash_new();
+    db.aset(ruby.to_symbol("reachable"), health.db.reachable)?;
+    db.aset(
+        ruby.to_symbol("latency_ms"),
+        health.db.latency.map(|d| d.as_secs_f64() * 1000.0),
+    )?;
+    db.aset(ruby.to_symbol("error"), health.db.error.clone())?;
+
+    let subscribers = ruby.ary_new();
+    for sub in &health.subscribers {
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("session_id"), sub.session_id.as_str())?;
+        hash.aset(ruby.to_symbol("state"), ruby.to_symbol(sub.state.as_str()))?;
+        subscribers.push(hash)?;
+    }
+
+    let hash = ruby.hash_new();
+    hash.aset(ruby.to_symbol("healthy"), health.is_healthy())?;
+    hash.aset(ruby.to_symbol("db"), db)?;
+    hash.aset(ruby.to_symbol("writer_alive"), health.writer_alive)?;
+    hash.aset(
+        ruby.to_symbol("writer_state"),
+        ruby.to_symbol(health.writer_state.as_str()),
+    )?;
+    let writer_last_failure_at = match health.writer_last_failure_at {
+        Some(time) => {
+            Some(ruby.time_nano_new(time.timestamp(), time.timestamp_subsec_nanos() as i64)?)
+        }
+        None => None,
+    };
+    hash.aset(
+        ruby.to_symbol("writer_last_failure_at"),
+        writer_last_failure_at,
+    )?;
+    hash.aset(ruby.to_symbol("queue_saturation"), health.queue_saturation)?;
+    hash.aset(ruby.to_symbol("subscribers"), subscribers)?;
+    hash.aset(ruby.to_symbol("dead_subscribers"), health.dead_subscribers)?;
+    Ok(hash)
+}
+
+/// Get engine metrics in Prometheus text format
+fn render_prometheus(ruby: &Ruby) -> Result<String, Error> {
+    let pubsub = engine::get(ruby)?;
+    let rt = runtime::get();
+
+    gvl::block_on(ruby, rt, pubsub.render_prometheus())?.map_err(ruby_error)
+}
+
+fn stats_to_hash(ruby: &Ruby, stats: &Stats) -> Result<RHash, Error> {
+    let writer = ruby.hash_new();
+    let w = &stats.writer;
+    writer.aset(ruby.to_symbol("queue_depth"), w.queue_depth)?;
+    writer.aset(
+        ruby.to_symbol("priority_queue_depth"),
+        w.priority_queue_depth,
+    )?;
+    writer.aset(ruby.to_symbol("queue_capacity"), w.queue_capacity)?;
+    writer.aset(ruby.to_symbol("enqueued"), w.enqueued)?;
+    writer.aset(ruby.to_symbol("dropped"), w.dropped)?;
+    writer.aset(ruby.to_symbol("written"), w.written)?;
+    writer.aset(ruby.to_symbol("coalesced"), w.coalesced)?;
+    writer.aset(ruby.to_symbol("failed_batches"), w.failed_batches)?;
+    writer.aset(ruby.to_symbol("restarts"), w.restarts)?;
+    writer.aset(ruby.to_symbol("last_error"), w.last_error.clone())?;
+    writer.aset(
+        ruby.to_symbol("batch_size"),
+        histogram_to_hash(ruby, &w.batch_size)?,
+    )?;
+    writer.aset(
+        ruby.to_symbol("write_latency_us"),
+        histogram_to_hash(ruby, &w.write_latency_us)?,
+    )?;
+
+    let subscribers = ruby.ary_new();
+    for sub in &stats.subscribers {
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("session_id"), sub.session_id.as_str())?;
+        hash.aset(ruby.to_symbol("delivered"), sub.delivered)?;
+        hash.aset(ruby.to_symbol("cursor"), sub.cursor)?;
+        hash.aset(ruby.to_symbol("behind_head"), sub.behind_head)?;
+        hash.aset(ruby.to_symbol("restarts"), sub.restarts)?;
+        hash.aset(ruby.to_symbol("last_error"), sub.last_error.clone())?;
+        subscribers.push(hash)?;
+    }
+
+    let db = ruby.hash_new();
+    db.aset(ruby.to_symbol("backend"), stats.db.backend)?;
+    db.aset(ruby.to_symbol("size"), stats.db.size)?;
+    db.aset(ruby.to_symbol("idle"), stats.db.idle)?;
+    db.aset(ruby.to_symbol("max_connections"), stats.db.max_connections)?;
+
+    let hash = ruby.hash_new();
+    hash.aset(ruby.to_symbol("writer"), writer)?;
+    hash.aset(ruby.to_symbol("subscribers"), subscribers)?;
+    hash.aset(ruby.to_symbol("inbox_dropped"), stats.inbox_dropped)?;
+    hash.aset(ruby.to_symbol("mailbox_dropped"), subscription::dropped())?;
+    hash.aset(ruby.to_symbol("db"), db)?;
+    Ok(hash)
+}
 
-        Ok(rt.block_on(async { pubsub.subscription_count().await }))
-    })
+fn histogram_to_hash(ruby: &Ruby, histogram: &HistogramSnapshot) -> Result<RHash, Error> {
+    let hash = ruby.hash_new();
+    hash.aset(ruby.to_symbol("buckets"), histogram.buckets.clone())?;
+    hash.aset(ruby.to_symbol("sum"), histogram.sum)?;
+    hash.aset(ruby.to_symbol("count"), histogram.count)?;
+    Ok(hash)
 }
 
 #[magnus::init]
 fn init(ruby: &Ruby) -> Result<(), Error> {
     let module = ruby.define_module("SolidMCPNative")?;
+    errors::define(ruby);
 
     // Core functions
     module.define_module_function("version", function!(version, 0))?;
     module.define_module_function("initialized?", function!(initialized, 0))?;
 
     // Lifecycle
-    module.define_module_function("init", function!(init_engine, 1))?;
+    module.define_module_function("configure_runtime", function!(configure_runtime, -1))?;
+    module.define_module_function("init", function!(init_engine, -1))?;
     module.define_module_function("init_with_config", function!(init_engine_with_config, 4))?;
     module.define_module_function("shutdown", function!(shutdown, 0))?;
+    module.define_module_function("after_fork", function!(after_fork, 0))?;
 
     // Messaging
     module.define_module_function("broadcast", function!(broadcast, 3))?;
+    module.define_module_function("broadcast_traced", function!(broadcast_traced, 4))?;
+    module.define_module_function("subscribe", function!(subscribe, 1))?;
+    module.define_module_function("unsubscribe", function!(unsubscribe, 1))?;
+    module.define_module_function("receive", function!(receive, 2))?;
     module.define_module_function("flush", function!(flush, 0))?;
     module.define_module_function("mark_delivered", function!(mark_delivered, 1))?;
     module.define_module_function("cleanup", function!(cleanup, 0))?;
 
     // Status
     module.define_module_function("subscription_count", function!(subscription_count, 0))?;
+    module.define_module_function("stats", function!(stats, 0))?;
+    module.define_module_function("health", function!(health, 0))?;
+    module.define_module_function("render_prometheus", function!(render_prometheus, 0))?;
 
     Ok(())
 }
@@ -0,0 +1,105 @@
+//! Engine options passed from Ruby
+//!
+//! `SolidMCPNative.init(url, **options)` accepts one key per [`Config`] field.
+//! Values go through [`Config::set`], the same parser used for environment
+//! variables and TOML files. Durations are given in seconds (Integer or
+//! Float), matching `SolidMCP::Configuration`, or as strings with a unit.
+
+use magnus::r_hash::ForEach;
+use magnus::{Error, Integer, RHash, RString, ReprValue, Ruby, Symbol, TryConvert, Value};
+use solid_mcp_core::config::KEYS;
+use solid_mcp_core::{Config, Error as CoreError};
+
+/// Build a [`Config`] from a database URL and an options Hash
+///
+/// Keys may be Symbols or Strings and are any of [`KEYS`] except
+/// `database_url`. Each value is converted to the string form
+/// [`Config::set`] parses: numbers as written (seconds for durations),
+/// Symbols by name and `nil` as empty, which unsets optional settings.
+/// Unknown keys and invalid values raise `ArgumentError`.
+pub fn config_from_hash(
+    ruby: &Ruby,
+    database_url: String,
+    options: RHash,
+) -> Result<Config, Error> {
+    let mut config = Config::new(database_url);
+    options.foreach(|key: Value, value: Value| {
+        let name = option_name(ruby, key)?;
+        if name == "database_url" || !KEYS.contains(&name.as_str()) {
+            return Err(arg_error(
+                ruby,
+                format!(
+                    "unknown option :{} (expected one of: {})",
+                    name,
+                    option_keys().collect::<Vec<_>>().join(", ")
+                ),
+            ));
+        }
+        let value = option_value(ruby, &name, value)?;
+        config.set(&name, &value).map_err(|e| match e {
+            CoreError::Config(message) => arg_error(ruby, message),
+            other => arg_error(ruby, other.to_string()),
+        })?;
+        Ok(ForEach::Continue)
+    })?;
+    Ok(config)
+}
+
+/// Option keys accepted by [`config_from_hash`]
+fn option_keys() -> impl Iterator<Item = &'static str> {
+    KEYS.iter().copied().filter(|key| *key != "database_url")
+}
+
+fn option_value(ruby: &Ruby, name: &str, value: Value) -> Result<String, Error> {
+    if value.is_nil() {
+        return Ok(String::new());
+    }
+    if value.is_kind_of(ruby.class_true_class()) {
+        return Ok("true".to_string());
+    }
+    if value.is_kind_of(ruby.class_false_class()) {
+        return Ok("false".to_string());
+    }
+    if let Some(symbol) = Symbol::from_value(value) {
+        return Ok(symbol.name()?.into_owned());
+    }
+    if let Some(string) = RString::from_value(value) {
+        return string.to_string();
+    }
+    if let Some(integer) = Integer::from_value(value) {
+        return Ok(integer.to_string());
+    }
+    // Floats and other Numerics, such as Rational or ActiveSupport::Duration
+    f64::try_convert(value)
+        .map(|secs| secs.to_string())
+        .map_err(|_| {
+            arg_error(
+                ruby,
+                format!(
+                    "invalid {}: expected a String, Symbol, number, boolean or nil, got {}",
+                    name,
+                    value.inspect()
+                ),
+            )
+        })
+}
+
+fn option_name(ruby: &Ruby, key: Value) -> Result<String, Error> {
+    if let Some(symbol) = Symbol::from_value(key) {
+        return Ok(symbol.name()?.into_owned());
+    }
+    if let Some(string) = RString::from_value(key) {
+        return string.to_string();
+    }
+    Err(arg_error(
+        ruby,
+        format!(
+            "option keys must be Symbols or Strings, got {}",
+            key.inspect()
+        ),
+    ))
+}
+
+fn arg_error(ruby: &Ruby, message: String) -> Error {
+    Error::new(ruby.exception_arg_error(), message)
+}
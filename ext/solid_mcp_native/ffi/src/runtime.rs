@@ -0,0 +1,135 @@
+//! Tokio runtime for the extension
+//!
+//! The runtime is created on first use from the [`RuntimeOptions`] set by
+//! [`configure`], and tied to the current PID. A forked child inherits the
+//! parent's runtime without its worker threads, so [`get`] builds a new
+//! runtime whenever the PID changes. The parent's runtime is leaked in the
+//! child: shutting it down would wait on threads that do not exist there.
+
+use solid_mcp_core::{Error, Result};
+use std::process;
+use std::sync::{LazyLock, RwLock};
+use tokio::runtime::{Builder, Runtime};
+use tracing::info;
+
+/// Tokio scheduler flavour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    /// Work-stealing scheduler with `worker_threads` threads (default)
+    MultiThread,
+    /// Single scheduler thread, for small containers
+    CurrentThread,
+}
+
+/// Settings used when building the runtime
+#[derive(Debug, Clone)]
+pub struct RuntimeOptions {
+    /// Scheduler flavour
+    pub flavor: Flavor,
+    /// Worker threads for [`Flavor::MultiThread`] (default: 4)
+    pub worker_threads: usize,
+    /// Name of runtime threads (default: `solid-mcp-worker`)
+    pub thread_name: String,
+    /// Upper bound for the blocking thread pool (default: 512)
+    pub max_blocking_threads: usize,
+}
+
+impl Default for RuntimeOptions {
+    fn default() -> Self {
+        Self {
+            flavor: Flavor::MultiThread,
+            worker_threads: 4,
+            thread_name: "solid-mcp-worker".to_string(),
+            max_blocking_threads: 512,
+        }
+    }
+}
+
+struct ProcessRuntime {
+    runtime: &'static Runtime,
+    pid: u32,
+}
+
+#[derive(Default)]
+struct State {
+    options: RuntimeOptions,
+    current: Option<ProcessRuntime>,
+}
+
+static STATE: LazyLock<RwLock<State>> = LazyLock::new(Default::default);
+
+fn build(options: &RuntimeOptions) -> &'static Runtime {
+    let mut builder = match options.flavor {
+        Flavor::MultiThread => {
+            let mut builder = Builder::new_multi_thread();
+            builder.worker_threads(options.worker_threads);
+            builder
+        }
+        Flavor::CurrentThread => Builder::new_current_thread(),
+    };
+
+    let runtime = builder
+        .enable_all()
+        .thread_name(&options.thread_name)
+        .max_blocking_threads(options.max_blocking_threads)
+        .build()
+        .expect("Failed to create Tokio runtime");
+    let runtime: &'static Runtime = Box::leak(Box::new(runtime));
+
+    // A current-thread runtime only runs spawned tasks (writer, subscribers)
+    // while some thread drives it
+    if options.flavor == Flavor::CurrentThread {
+        std::thread::Builder::new()
+            .name(options.thread_name.clone())
+            .spawn(|| runtime.block_on(std::future::pending::<()>()))
+            .expect("Failed to spawn Tokio driver thread");
+    }
+
+    runtime
+}
+
+/// Set the options for the runtime
+///
+/// Fails if the runtime has already been started in this process.
+pub fn configure(options: RuntimeOptions) -> Result<()> {
+    let mut state = STATE.write().unwrap_or_else(|e| e.into_inner());
+    if state
+        .current
+        .as_ref()
+        .is_some_and(|current| current.pid == process::id())
+    {
+        return Err(Error::Config(
+            "runtime already started; configure it before first use".to_string(),
+        ));
+    }
+
+    state.options = options;
+    Ok(())
+}
+
+/// Get the runtime for the current process, building it if needed
+pub fn get() -> &'static Runtime {
+    let pid = process::id();
+    if let Some(current) = STATE
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .current
+        .as_ref()
+        && current.pid == pid
+    {
+        return current.runtime;
+    }
+
+    let mut state = STATE.write().unwrap_or_else(|e| e.into_inner());
+    match state.current.as_ref() {
+        Some(current) if current.pid == pid => current.runtime,
+        previous => {
+            if previous.is_some() {
+                info!("Fork detected (pid {}), rebuilding Tokio runtime", pid);
+            }
+            let runtime = build(&state.options);
+            state.current = Some(ProcessRuntime { runtime, pid });
+            runtime
+        }
+    }
+}
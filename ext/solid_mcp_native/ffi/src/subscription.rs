@@ -0,0 +1,182 @@
+//! Ruby block subscriptions
+//!
+//! Core subscribers invoke their callback on Tokio worker threads, which must
+//! not touch Ruby objects. Each Ruby subscription gets a [`Mailbox`] filled by
+//! the core callback and a Ruby dispatcher thread that waits on it without the
+//! GVL, then calls the block with the GVL held. A slow block can't grow the
+//! mailbox without limit: once full, the oldest messages are dropped and
+//! counted in [`dropped`].
+
+use crate::gvl::without_gvl;
+use magnus::value::Opaque;
+use magnus::{Error, Object, RHash, Ruby, Thread, Value, block::Proc};
+use solid_mcp_core::Message;
+use solid_mcp_core::subscriber::MessageCallback;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// Messages dropped from full mailboxes, across all subscriptions
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of messages dropped because a mailbox was full
+pub fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Queue of messages waiting to be handed to Ruby
+pub struct Mailbox {
+    state: Mutex<MailboxState>,
+    ready: Condvar,
+    capacity: usize,
+}
+
+#[derive(Default)]
+struct MailboxState {
+    messages: VecDeque<Message>,
+    closed: bool,
+    interrupted: bool,
+}
+
+impl Mailbox {
+    /// Create a mailbox holding up to `capacity` messages
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::default(),
+            ready: Condvar::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MailboxState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn push(&self, message: Message) {
+        let mut state = self.lock();
+        if state.messages.len() >= self.capacity {
+            state.messages.pop_front();
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+        state.messages.push_back(message);
+        drop(state);
+        self.ready.notify_one();
+    }
+
+    /// Stop accepting messages; waiters return once the queue is drained
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.ready.notify_all();
+    }
+
+    /// Wake a waiter so Ruby can handle a pending interrupt
+    pub fn interrupt(&self) {
+        self.lock().interrupted = true;
+        self.ready.notify_all();
+    }
+
+    /// Block until messages are available
+    ///
+    /// Returns `None` once the mailbox is closed and drained, and an empty
+    /// batch when interrupted.
+    pub fn wait(&self) -> Option<Vec<Message>> {
+        let mut state = self.lock();
+        loop {
+            if !state.messages.is_empty() {
+                return Some(state.messages.drain(..).collect());
+            }
+            if state.closed {
+                return None;
+            }
+            if std::mem::take(&mut state.interrupted) {
+                return Some(Vec::new());
+            }
+            state = self.ready.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+/// Sender half owned by the core callback
+///
+/// The core subscriber drops its callback when it stops, which closes the
+/// mailbox and ends the dispatcher thread.
+struct MailboxSender(Arc<Mailbox>);
+
+impl Drop for MailboxSender {
+    fn drop(&mut self) {
+        self.0.close();
+    }
+}
+
+/// Build a core callback that feeds `mailbox`
+pub fn callback(mailbox: Arc<Mailbox>) -> MessageCallback {
+    let sender = MailboxSender(mailbox);
+    Box::new(move |message| sender.0.push(message))
+}
+
+/// Start a Ruby thread that calls `block` with each message from `mailbox`
+pub fn spawn_dispatcher(
+    ruby: &Ruby,
+    session_id: &str,
+    block: Proc,
+    mailbox: Arc<Mailbox>,
+) -> Result<Thread, Error> {
+    let opaque = Opaque::from(block);
+    let thread =
+        ruby.thread_create_from_fn(move |ruby| dispatch(ruby, ruby.get_inner(opaque), &mailbox));
+
+    // Keep the block reachable by the GC for the lifetime of the thread
+    thread.ivar_set("__solid_mcp_block", block)?;
+    let _: Value = thread.funcall("name=", (format!("solid_mcp:{}", session_id),))?;
+    Ok(thread)
+}
+
+/// Call `block` with each message until the mailbox is closed
+///
+/// Waits go through [`without_gvl`], so Ruby never raises inside them.
+/// `Thread#kill` or `Thread#raise` on the dispatcher wakes the wait and
+/// takes effect at `thread_check_ints`, whose error returns normally and
+/// drops any batch already drained.
+fn dispatch(ruby: &Ruby, block: Proc, mailbox: &Mailbox) -> Result<(), Error> {
+    while let Some(messages) =
+        without_gvl(|| mailbox.wait(), || mailbox.interrupt()).unwrap_or(Some(Vec::new()))
+    {
+        ruby.thread_check_ints()?;
+
+        for message in messages {
+            let hash = message_to_hash(ruby, &message)?;
+            if let Err(e) = block.call::<_, Value>((hash,)) {
+                // Let kill/exit and non-StandardError exceptions end the thread
+                if !e.is_kind_of(ruby.exception_standard_error()) {
+                    return Err(e);
+                }
+                tracing::error!(
+                    "SolidMCP callback error for session {}: {}",
+                    message.session_id,
+                    e
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Convert a message into `{id:, session_id:, event_type:, data:, created_at:, traceparent:}`
+pub fn message_to_hash(ruby: &Ruby, message: &Message) -> Result<RHash, Error> {
+    let created_at = ruby.time_nano_new(
+        message.created_at.timestamp(),
+        message.created_at.timestamp_subsec_nanos() as i64,
+    )?;
+
+    let hash = ruby.hash_new();
+    hash.aset(ruby.to_symbol("id"), message.id)?;
+    hash.aset(ruby.to_symbol("session_id"), message.session_id.as_str())?;
+    hash.aset(ruby.to_symbol("event_type"), message.event_type.as_str())?;
+    hash.aset(ruby.to_symbol("data"), message.data.as_str())?;
+    hash.aset(ruby.to_symbol("created_at"), created_at)?;
+    hash.aset(
+        ruby.to_symbol("traceparent"),
+        message.traceparent.as_deref(),
+    )?;
+    Ok(hash)
+}
@@ -26,6 +26,19 @@ module SolidMCP
       @undelivered_retention.seconds
     end
 
+    # Options for SolidMCPNative.init, durations in seconds
+    def native_options
+      {
+        batch_size: batch_size,
+        polling_interval: polling_interval.to_f,
+        max_wait_time: max_wait_time.to_f,
+        delivered_retention: delivered_retention.to_f,
+        undelivered_retention: undelivered_retention.to_f,
+        max_queue_size: max_queue_size,
+        shutdown_timeout: shutdown_timeout.to_f
+      }
+    end
+
     private
 
     def default_logger
@@ -1,5 +1,8 @@
 # frozen_string_literal: true
 
+require "erb"
+require "uri"
+
 # Native Rust acceleration for SolidMCP (optional)
 #
 # This module provides a Rust-powered pub/sub engine using Tokio for async I/O.
@@ -30,6 +33,7 @@ module SolidMCP
 
         begin
           require "solid_mcp_native/solid_mcp_native"
+          install_fork_hook
           log_info "SolidMCP native extension loaded (v#{SolidMCPNative.version})"
           true
         rescue LoadError => e
@@ -38,6 +42,15 @@ module SolidMCP
         end
       end
 
+      # Rebuild the native runtime and engine in forked children (Puma/Unicorn
+      # workers). Process._fork is available on Ruby 3.1+; older Rubies rely on
+      # lazy PID detection on the next native call.
+      def install_fork_hook
+        return unless Process.respond_to?(:_fork)
+
+        Process.singleton_class.prepend(ForkHook)
+      end
+
       def log_info(msg)
         SolidMCP::Logger.info(msg)
       rescue StandardError
@@ -49,6 +62,31 @@ module SolidMCP
       rescue StandardError
         # Logger not ready, silently ignore
       end
+
+      def log_error(msg)
+        SolidMCP::Logger.error(msg)
+      rescue StandardError
+        # Logger not ready, silently ignore
+      end
+    end
+
+    # Calls SolidMCPNative.after_fork in the child after every fork
+    #
+    # Errors are logged rather than raised: raising here would make
+    # Kernel#fork fail in the child, which would then carry on down the
+    # parent's code path. The next native call retries the reconnect.
+    module ForkHook
+      def _fork
+        pid = super
+        if pid.zero?
+          begin
+            SolidMCPNative.after_fork if SolidMCPNative.initialized?
+          rescue StandardError => e
+            SolidMCP::NativeSpeedup.send(:log_error, "SolidMCP native after_fork failed: #{e.class}: #{e.message}")
+          end
+        end
+        pid
+      end
     end
 
     # Override MessageWriter with native implementation
@@ -66,12 +104,7 @@ module SolidMCP
           db_config = SolidMCP.configuration.database_config
           database_url = build_database_url(db_config)
 
-          SolidMCPNative.init_with_config(
-            database_url,
-            SolidMCP.configuration.batch_size,
-            (SolidMCP.configuration.polling_interval * 1000).to_i, # Convert to ms
-            SolidMCP.configuration.max_queue_size
-          )
+          SolidMCPNative.init(database_url, **SolidMCP.configuration.native_options)
           @native_initialized = true
         else
           super
@@ -112,22 +145,56 @@ module SolidMCP
         case adapter
         when "sqlite3"
           # SQLite URL format
-          database = config[:database] || ":memory:"
-          "sqlite://#{database}"
+          database = escape_sqlite_path(config[:database] || ":memory:")
+          "sqlite://#{database}#{url_query(pool_size: config[:pool], busy_timeout: config[:timeout])}"
         when "postgresql", "postgres"
-          # PostgreSQL URL format
+          # PostgreSQL URL format (credentials must be percent-encoded)
           host = config[:host] || "localhost"
           port = config[:port] || 5432
           database = config[:database] || "solid_mcp"
           username = config[:username]
           password = config[:password]
 
-          auth = username ? "#{username}:#{password}@" : ""
-          "postgres://#{auth}#{host}:#{port}/#{database}"
+          auth = ""
+          if username
+            auth = ERB::Util.url_encode(username.to_s)
+            auth += ":#{ERB::Util.url_encode(password.to_s)}" if password
+            auth += "@"
+          end
+
+          # Unix socket directories go in the host parameter, IPv6 addresses
+          # need brackets
+          socket = nil
+          host = host.to_s
+          if host.start_with?("/")
+            socket = host
+            host = "localhost"
+          elsif host.include?(":") && !host.start_with?("[")
+            host = "[#{host}]"
+          end
+
+          query = url_query(
+            host: socket,
+            sslmode: config[:sslmode],
+            application_name: config[:application_name],
+            pool_size: config[:pool]
+          )
+          "postgres://#{auth}#{host}:#{port}/#{ERB::Util.url_encode(database.to_s)}#{query}"
         else
           raise SolidMCP::Error, "Unsupported database adapter: #{adapter}"
         end
       end
+
+      # Percent-encode characters that would end the path early; the rest of
+      # the path, including ":memory:", is passed through as written
+      def escape_sqlite_path(path)
+        path.to_s.gsub(/[%?#]/) { |c| format("%%%02X", c.ord) }
+      end
+
+      def url_query(params)
+        params = params.compact
+        params.empty? ? "" : "?#{URI.encode_www_form(params)}"
+      end
     end
   end
 end
@@ -39,6 +39,17 @@ module SolidMCP
       assert_equal 60, @config.max_wait_time
     end
 
+    def test_native_options
+      @config.delivered_retention = 2.hours
+
+      options = @config.native_options
+      assert_equal 200, options[:batch_size]
+      assert_equal 0.1, options[:polling_interval]
+      assert_equal 7200.0, options[:delivered_retention]
+      assert_equal 86400.0, options[:undelivered_retention]
+      assert_equal 30.0, options[:shutdown_timeout]
+    end
+
     def test_retention_can_be_set_in_seconds
       @config.delivered_retention = 7200
       assert_equal 7200.seconds, @config.delivered_retention
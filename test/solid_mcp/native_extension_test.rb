@@ -0,0 +1,278 @@
+# frozen_string_literal: true
+
+require "test_helper"
+require "securerandom"
+require "sqlite3"
+require "tmpdir"
+
+module SolidMCP
+  # Exercises the compiled extension; skipped when it isn't built
+  class NativeExtensionTest < ActiveSupport::TestCase
+    SCHEMA = <<~SQL
+      CREATE TABLE solid_mcp_messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+        session_id varchar(36) NOT NULL,
+        event_type varchar(50) NOT NULL,
+        data text,
+        created_at datetime(6) NOT NULL,
+        delivered_at datetime(6)
+      );
+      CREATE INDEX idx_solid_mcp_messages_on_session_and_id ON solid_mcp_messages (session_id, id);
+      CREATE INDEX idx_solid_mcp_messages_on_delivered_and_created ON solid_mcp_messages (delivered_at, created_at);
+    SQL
+
+    def setup
+      skip "native extension not available" unless NativeSpeedup.available?
+
+      @dir = Dir.mktmpdir("solid_mcp_native")
+      @path = File.join(@dir, "messages.sqlite3")
+      SQLite3::Database.open(@path) { |db| db.execute_batch(SCHEMA) }
+      @url = "sqlite://#{@path}?mode=rwc"
+      @session_id = SecureRandom.uuid
+    end
+
+    def teardown
+      return unless @dir
+
+      SolidMCPNative.shutdown
+      FileUtils.remove_entry(@dir)
+    end
+
+    def test_subscribe_delivers_to_block
+      SolidMCPNative.init(@url, polling_interval: 0.01)
+      received = Queue.new
+      SolidMCPNative.subscribe(@session_id) { |message| received << message }
+
+      assert_raises(SolidMCPNative::AlreadySubscribed) do
+        SolidMCPNative.subscribe(@session_id) { |_message| }
+      end
+
+      SolidMCPNative.broadcast(@session_id, "message", '{"n":1}')
+      SolidMCPNative.flush
+
+      assert wait_for_condition(5) { !received.empty? }
+      message = received.pop
+      assert_equal @session_id, message[:session_id]
+      assert_equal "message", message[:event_type]
+      assert_equal '{"n":1}', message[:data]
+      assert_kind_of Time, message[:created_at]
+    ensure
+      SolidMCPNative.unsubscribe(@session_id) if SolidMCPNative.initialized?
+    end
+
+    def test_block_errors_do_not_stop_delivery
+      SolidMCPNative.init(@url, polling_interval: 0.01)
+      received = Queue.new
+      SolidMCPNative.subscribe(@session_id) do |message|
+        raise "boom" if message[:data] == "bad"
+
+        received << message
+      end
+
+      SolidMCPNative.broadcast(@session_id, "message", "bad")
+      SolidMCPNative.broadcast(@session_id, "message", "good")
+      SolidMCPNative.flush
+
+      assert wait_for_condition(5) { !received.empty? }
+      assert_equal "good", received.pop[:data]
+    ensure
+      SolidMCPNative.unsubscribe(@session_id) if SolidMCPNative.initialized?
+    end
+
+    def test_stats_report_dropped_mailbox_messages
+      SolidMCPNative.init(@url)
+      assert_kind_of Integer, SolidMCPNative.stats[:mailbox_dropped]
+    end
+
+    def test_exception_hierarchy
+      assert_operator SolidMCPNative::Error, :<, StandardError
+      %i[NotInitialized AlreadySubscribed QueueFull ShutdownError DatabaseError].each do |name|
+        assert_operator SolidMCPNative.const_get(name), :<, SolidMCPNative::Error
+      end
+      %i[ConnectionLost PoolTimeout ConstraintViolation SchemaMismatch].each do |name|
+        assert_operator SolidMCPNative.const_get(name), :<, SolidMCPNative::DatabaseError
+      end
+      assert SolidMCPNative::DatabaseError.method_defined?(:sqlstate)
+      assert SolidMCPNative::DatabaseError.method_defined?(:db_message)
+    end
+
+    def test_not_initialized
+      refute SolidMCPNative.initialized?
+      assert_raises(SolidMCPNative::NotInitialized) do
+        SolidMCPNative.broadcast(@session_id, "message", "{}")
+      end
+    end
+
+    def test_missing_table_raises
+      File.delete(@path)
+      assert_raises(SolidMCPNative::Error) { SolidMCPNative.init(@url) }
+    end
+
+    def test_receive_returns_messages
+      SolidMCPNative.init(@url, polling_interval: 0.01)
+      assert_equal [], SolidMCPNative.receive(@session_id, 10)
+
+      SolidMCPNative.broadcast(@session_id, "message", "{}")
+      SolidMCPNative.flush
+
+      messages = []
+      assert wait_for_condition(5) { messages.concat(SolidMCPNative.receive(@session_id, 100)).any? }
+      assert_equal "{}", messages.first[:data]
+      assert_kind_of Integer, SolidMCPNative.stats[:inbox_dropped]
+    ensure
+      SolidMCPNative.unsubscribe(@session_id) if SolidMCPNative.initialized?
+    end
+
+    def test_receive_rejects_block_subscribed_session
+      SolidMCPNative.init(@url)
+      SolidMCPNative.subscribe(@session_id) { |_message| }
+
+      assert_raises(SolidMCPNative::AlreadySubscribed) do
+        SolidMCPNative.receive(@session_id, 10)
+      end
+    ensure
+      SolidMCPNative.unsubscribe(@session_id) if SolidMCPNative.initialized?
+    end
+
+    def test_receive_releases_gvl
+      SolidMCPNative.init(@url)
+      ticks = 0
+      ticker = Thread.new { loop { ticks += 1; sleep 0.001 } }
+
+      SolidMCPNative.receive(@session_id, 300)
+      assert_operator ticks, :>, 10
+    ensure
+      ticker&.kill
+    end
+
+    def test_receive_is_interruptible
+      SolidMCPNative.init(@url, max_wait_time: 30)
+      waiter = Thread.new { SolidMCPNative.receive(@session_id, 30_000) }
+      sleep 0.1
+      waiter.raise(Interrupt)
+
+      started = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+      assert_raises(Interrupt) { waiter.join }
+      assert_operator Process.clock_gettime(Process::CLOCK_MONOTONIC) - started, :<, 5
+    end
+
+    def test_flush_survives_interrupts_that_do_not_raise
+      skip "SIGUSR1 not supported" unless Signal.list.key?("USR1")
+
+      SolidMCPNative.init(@url)
+      previous = trap("USR1") { nil }
+      SolidMCPNative.broadcast(@session_id, "message", "{}")
+
+      Process.kill("USR1", Process.pid)
+      assert SolidMCPNative.flush
+    ensure
+      trap("USR1", previous || "DEFAULT")
+    end
+
+    def test_threads_share_engine
+      SolidMCPNative.init(@url)
+      results = Array.new(4) do |i|
+        Thread.new { SolidMCPNative.broadcast(@session_id, "message", i.to_s) }
+      end.map(&:value)
+      assert_equal [true] * 4, results
+      SolidMCPNative.flush
+
+      count = SQLite3::Database.open(@path) do |db|
+        db.get_first_value("SELECT COUNT(*) FROM solid_mcp_messages WHERE session_id = ?", [@session_id])
+      end
+      assert_equal 4, count
+    end
+
+    def test_shutdown_while_threads_hold_engine
+      SolidMCPNative.init(@url)
+      waiter = Thread.new { SolidMCPNative.receive(@session_id, 5_000) }
+      waiter.report_on_exception = false
+      sleep 0.1
+
+      assert SolidMCPNative.shutdown
+      refute SolidMCPNative.initialized?
+
+      # The pending receive ends once its subscription is closed
+      assert_raises(SolidMCPNative::Error) { waiter.join(5) }
+    end
+
+    def test_engine_reinitializes_after_fork
+      skip "fork not supported" unless Process.respond_to?(:fork)
+
+      SolidMCPNative.init(@url)
+      pid = fork do
+        SolidMCPNative.broadcast(@session_id, "message", "from child")
+        SolidMCPNative.flush
+        exit!(SolidMCPNative.initialized? ? 0 : 1)
+      rescue Exception # rubocop:disable Lint/RescueException
+        exit!(2)
+      end
+      _, status = Process.wait2(pid)
+      assert status.success?, "child exited with #{status.exitstatus}"
+
+      rows = SQLite3::Database.open(@path) do |db|
+        db.execute("SELECT data FROM solid_mcp_messages WHERE session_id = ?", [@session_id])
+      end
+      assert_equal [["from child"]], rows
+
+      # The parent's engine is untouched
+      assert SolidMCPNative.broadcast(@session_id, "message", "from parent")
+      SolidMCPNative.flush
+    end
+
+    def test_fork_hook_failure_does_not_break_fork
+      skip "fork hook needs Process._fork" unless Process.respond_to?(:_fork)
+
+      require "minitest/mock"
+      SolidMCPNative.init(@url)
+      parent = Process.pid
+      failing = -> { raise SolidMCPNative::DatabaseError, "database unavailable" }
+
+      pid = SolidMCPNative.stub(:after_fork, failing) do
+        fork { exit!(0) }
+      rescue Exception # rubocop:disable Lint/RescueException
+        # Never let a child continue down the test's code path
+        exit!(3) unless Process.pid == parent
+        raise
+      end
+      _, status = Process.wait2(pid)
+      assert status.success?, "child exited with #{status.exitstatus}"
+    end
+
+    def test_configure_runtime_validates_options
+      error = assert_raises(ArgumentError) { SolidMCPNative.configure_runtime(flavor: :bogus) }
+      assert_match(/unknown runtime flavor/, error.message)
+
+      assert_raises(ArgumentError) { SolidMCPNative.configure_runtime(worker_threads: 0) }
+      assert_raises(ArgumentError) { SolidMCPNative.configure_runtime(max_blocking_threads: 0) }
+      assert_raises(ArgumentError) { SolidMCPNative.configure_runtime(bogus: 1) }
+    end
+
+    def test_configure_runtime_after_first_use_raises
+      SolidMCPNative.init(@url)
+
+      error = assert_raises(SolidMCPNative::Error) do
+        SolidMCPNative.configure_runtime(worker_threads: 2)
+      end
+      assert_match(/already started/, error.message)
+    end
+
+    def test_init_validates_options
+      error = assert_raises(ArgumentError) { SolidMCPNative.init(@url, bogus: 1) }
+      assert_match(/unknown option :bogus/, error.message)
+
+      error = assert_raises(ArgumentError) { SolidMCPNative.init(@url, batch_size: "many") }
+      assert_match(/batch_size/, error.message)
+
+      error = assert_raises(ArgumentError) { SolidMCPNative.init(@url, polling_interval: Object.new) }
+      assert_match(/polling_interval/, error.message)
+
+      refute SolidMCPNative.initialized?
+    end
+
+    def test_init_accepts_configuration_options
+      assert SolidMCPNative.init(@url, **SolidMCP.configuration.native_options, idle_timeout: nil)
+      assert SolidMCPNative.initialized?
+    end
+  end
+end